@@ -0,0 +1,348 @@
+//! Pattern statistics and a rough guess which skillset a pattern trains
+
+use super::*;
+
+/// The kind of pattern that dominates, similar to the Etterna skillsets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PatternClassification {
+	Stream,
+	Jumpstream,
+	Handstream,
+	Jack,
+	Chordjack,
+	Tech,
+}
+
+impl std::fmt::Display for PatternClassification {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Self::Stream => "Stream",
+			Self::Jumpstream => "Jumpstream",
+			Self::Handstream => "Handstream",
+			Self::Jack => "Jack",
+			Self::Chordjack => "Chordjack",
+			Self::Tech => "Tech",
+		})
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternAnalysis {
+	/// Number of notes that need to be hit. Mines and fakes are not counted
+	pub num_notes: usize,
+	/// Number of rows containing at least one note
+	pub num_rows: usize,
+	/// Number of rows for each chord size. Index 0 counts single notes, index 1 jumps, and so on
+	pub chord_size_counts: Vec<usize>,
+	/// Number of times a lane is hit in two consecutive rows
+	pub jack_count: usize,
+	/// Length of the longest jack in notes, and its lane. A lane that is never hit twice in a row
+	/// doesn't count as a jack
+	pub longest_jack: Option<(usize, usize)>,
+	/// Number of single-note rows that are part of a trill between two lanes
+	pub trill_rows: usize,
+	/// Number of single-note rows that are part of a roll, like `1234` or `4321`
+	pub roll_rows: usize,
+	/// Number of notes in each lane
+	pub lane_note_counts: Vec<usize>,
+	/// For each lane, the length in notes of the longest stretch in which the lane is hit at
+	/// least every other row
+	pub longest_anchors: Vec<usize>,
+	pub classification: PatternClassification,
+}
+
+impl PatternAnalysis {
+	/// Average number of notes per row
+	pub fn chord_density(&self) -> f32 {
+		self.num_notes as f32 / self.num_rows.max(1) as f32
+	}
+}
+
+/// Splits a pattern into its rows of notes, sorted by position. Each row is a sorted list of lanes
+pub(crate) fn note_rows(
+	pattern: &[(Pattern, FractionalSnap)],
+	keymode: usize,
+) -> Result<Vec<(usize, Vec<usize>)>, Error> {
+	let mut rows = std::collections::BTreeMap::<usize, Vec<usize>>::new();
+	for note in place_notes(pattern).notes {
+		let lane = note.lane.column_number_with_keymode(keymode as u32) as usize;
+		if lane >= keymode {
+			return Err(Error::InvalidLaneForKeymode {
+				human_readable_lane: lane + 1,
+				keymode,
+			});
+		}
+
+		match note.note_type {
+			NoteType::Mine | NoteType::Fake => continue,
+			NoteType::Tap | NoteType::Hold { .. } | NoteType::Roll { .. } | NoteType::Lift => {}
+		}
+		rows.entry(note.row).or_default().push(lane);
+	}
+
+	Ok(rows
+		.into_iter()
+		.map(|(position, mut lanes)| {
+			lanes.sort_unstable();
+			lanes.dedup();
+			(position, lanes)
+		})
+		.collect())
+}
+
+/// Counts the rows which are part of a run of single notes where each note satisfies `continues`
+/// given the two notes before it. Only runs of at least `min_length` notes are counted
+fn count_single_note_runs(
+	rows: &[(usize, Vec<usize>)],
+	min_length: usize,
+	continues: impl Fn(usize, usize, usize) -> bool,
+) -> usize {
+	let mut is_in_run = vec![false; rows.len()];
+	let mut run_start = 0;
+	for i in 0..rows.len() {
+		let continues_run = i >= 2
+			&& match (&*rows[i - 2].1, &*rows[i - 1].1, &*rows[i].1) {
+				(&[second_last], &[last], &[lane]) => continues(second_last, last, lane),
+				_ => false,
+			};
+		if !continues_run {
+			// any two single notes can be the start of a run
+			run_start = match (i.checked_sub(1).map(|i| &*rows[i].1), &*rows[i].1) {
+				(Some(&[_]), &[_]) => i - 1,
+				_ => i,
+			};
+		}
+
+		if i + 1 - run_start >= min_length {
+			for is_in_run in &mut is_in_run[run_start..=i] {
+				*is_in_run = true;
+			}
+		}
+	}
+
+	is_in_run.iter().filter(|&&is_in_run| is_in_run).count()
+}
+
+fn classify(
+	rows: &[(usize, Vec<usize>)],
+	chord_size_counts: &[usize],
+	jack_count: usize,
+	trill_rows: usize,
+	roll_rows: usize,
+) -> PatternClassification {
+	let num_rows = rows.len().max(1) as f32;
+	let share_of_rows = |count: usize| count as f32 / num_rows;
+	let chord_share =
+		|min_size: usize| share_of_rows(chord_size_counts.iter().skip(min_size - 1).sum::<usize>());
+	let jack_share = jack_count as f32 / (rows.len().max(2) - 1) as f32;
+
+	// Patterns that switch between many different rhythms, like 16ths mixed with 12ths, are tech
+	let mut gaps = rows
+		.windows(2)
+		.map(|pair| pair[1].0 - pair[0].0)
+		.collect::<Vec<_>>();
+	gaps.sort_unstable();
+	gaps.dedup();
+	let is_rhythmically_irregular = gaps.len() >= 3;
+
+	if jack_share >= 0.5 {
+		if chord_share(2) >= 0.5 {
+			PatternClassification::Chordjack
+		} else {
+			PatternClassification::Jack
+		}
+	} else if chord_share(3) >= 0.25 {
+		PatternClassification::Handstream
+	} else if chord_share(2) >= 0.25 {
+		PatternClassification::Jumpstream
+	} else if is_rhythmically_irregular || share_of_rows(trill_rows + roll_rows) >= 0.5 {
+		PatternClassification::Tech
+	} else {
+		PatternClassification::Stream
+	}
+}
+
+/// Computes statistics about the notes of a pattern. Mines and fakes are ignored
+pub fn analyze_pattern(
+	pattern: &[(Pattern, FractionalSnap)],
+	keymode: usize,
+) -> Result<PatternAnalysis, Error> {
+	let rows = note_rows(pattern, keymode)?;
+	if rows.is_empty() {
+		return Err(Error::EmptyPattern);
+	}
+
+	let num_notes = rows.iter().map(|(_, lanes)| lanes.len()).sum();
+
+	let mut chord_size_counts = vec![0; keymode];
+	for (_, lanes) in &rows {
+		chord_size_counts[lanes.len() - 1] += 1;
+	}
+
+	let mut lane_note_counts = vec![0; keymode];
+	let mut jack_count = 0;
+	// length and lane
+	let mut longest_jack = (0, 0);
+	let mut longest_anchors = vec![0; keymode];
+	for lane in 0..keymode {
+		let mut jack_length = 0;
+		let mut anchor_length = 0;
+		let mut last_hit_row_index = None;
+		for (row_index, (_, lanes)) in rows.iter().enumerate() {
+			if !lanes.contains(&lane) {
+				continue;
+			}
+			lane_note_counts[lane] += 1;
+
+			let rows_since_last_hit = last_hit_row_index.map(|last| row_index - last);
+			if rows_since_last_hit == Some(1) {
+				jack_count += 1;
+				jack_length += 1;
+			} else {
+				jack_length = 1;
+			}
+			if jack_length > longest_jack.0 {
+				longest_jack = (jack_length, lane);
+			}
+
+			anchor_length = match rows_since_last_hit {
+				Some(1) | Some(2) => anchor_length + 1,
+				_ => 1,
+			};
+			longest_anchors[lane] = longest_anchors[lane].max(anchor_length);
+
+			last_hit_row_index = Some(row_index);
+		}
+	}
+
+	let trill_rows = count_single_note_runs(&rows, 4, |second_last, last, lane| {
+		lane == second_last && lane != last
+	});
+	let roll_rows = count_single_note_runs(&rows, 4, |second_last, last, lane| {
+		// the direction must stay the same, and may wrap around from the last lane to the first
+		let step = |from: usize, to: usize| (to + keymode - from) % keymode;
+		let direction = step(second_last, last);
+		keymode >= 3
+			&& (direction == 1 || direction == keymode - 1)
+			&& step(last, lane) == direction
+	});
+
+	let classification = classify(&rows, &chord_size_counts, jack_count, trill_rows, roll_rows);
+
+	Ok(PatternAnalysis {
+		num_notes,
+		num_rows: rows.len(),
+		chord_size_counts,
+		jack_count,
+		longest_jack: Some(longest_jack).filter(|&(length, _)| length >= 2),
+		trill_rows,
+		roll_rows,
+		lane_note_counts,
+		longest_anchors,
+		classification,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn analyze(pattern: &str) -> PatternAnalysis {
+		let pattern = parse_pattern(pattern, 1000).unwrap();
+		let snap = FractionalSnap::from_snap_number(16).unwrap();
+		analyze_pattern(&[(pattern, snap)], 4).unwrap()
+	}
+
+	#[test]
+	fn chord_sizes() {
+		let analysis = analyze("[12]3[123][1234]4");
+		assert_eq!(analysis.num_notes, 11);
+		assert_eq!(analysis.num_rows, 5);
+		assert_eq!(analysis.chord_size_counts, [2, 1, 1, 1]);
+		assert_eq!(analysis.lane_note_counts, [3, 3, 3, 2]);
+		assert_eq!(analysis.chord_density(), 2.2);
+	}
+
+	#[test]
+	fn mines_and_fakes_are_ignored() {
+		// The pattern syntax has no fakes
+		let row = |notes: &[(u32, NoteType)]| Row {
+			notes: notes
+				.iter()
+				.map(|&(lane, note_type)| (Lane::Index(lane), note_type))
+				.collect(),
+		};
+		let pattern = Pattern {
+			rows: vec![
+				row(&[(0, NoteType::Tap), (1, NoteType::Mine)]),
+				row(&[(2, NoteType::Mine)]),
+				row(&[(2, NoteType::Fake)]),
+				row(&[(3, NoteType::Tap), (0, NoteType::Fake)]),
+			],
+		};
+		let snap = FractionalSnap::from_snap_number(16).unwrap();
+		let analysis = analyze_pattern(&[(pattern, snap)], 4).unwrap();
+		assert_eq!(analysis.num_notes, 2);
+		assert_eq!(analysis.num_rows, 2);
+		assert_eq!(analysis.lane_note_counts, [1, 0, 0, 1]);
+	}
+
+	#[test]
+	fn classification() {
+		let classify = |pattern| analyze(pattern).classification;
+		assert_eq!(classify("{1324}*4"), PatternClassification::Stream);
+		assert_eq!(
+			classify("{[12]3[24]1}*4"),
+			PatternClassification::Jumpstream
+		);
+		assert_eq!(
+			classify("{[123]4[12]3}*4"),
+			PatternClassification::Handstream
+		);
+		assert_eq!(classify("1111 2222"), PatternClassification::Jack);
+		assert_eq!(
+			classify("{[12]}*4 {[34]}*4"),
+			PatternClassification::Chordjack
+		);
+		assert_eq!(classify("{12}*8"), PatternClassification::Tech);
+	}
+
+	#[test]
+	fn jacks() {
+		let analysis = analyze("1111 2 [23] 3");
+		// three in lane 1, then 2-[23] and [23]-3
+		assert_eq!(analysis.jack_count, 5);
+		assert_eq!(analysis.longest_jack, Some((4, 0)));
+
+		let analysis = analyze("1234");
+		assert_eq!(analysis.jack_count, 0);
+		assert_eq!(analysis.longest_jack, None);
+	}
+
+	#[test]
+	fn anchors() {
+		// lane 1 is hit every other row, then the anchor is broken by two other notes
+		let analysis = analyze("1213141 23 1");
+		assert_eq!(analysis.longest_anchors, [4, 1, 1, 1]);
+	}
+
+	#[test]
+	fn trills() {
+		assert_eq!(analyze("1212 3").trill_rows, 4);
+		// too short to count
+		assert_eq!(analyze("121 3").trill_rows, 0);
+		// a chord interrupts the trill
+		assert_eq!(analyze("12[12]12").trill_rows, 0);
+		assert_eq!(analyze("1212").roll_rows, 0);
+	}
+
+	#[test]
+	fn rolls() {
+		// wrapping around from the last lane to the first continues the roll
+		assert_eq!(analyze("12341234").roll_rows, 8);
+		assert_eq!(analyze("4321 3").roll_rows, 4);
+		// changing direction ends the roll
+		assert_eq!(analyze("123 21").roll_rows, 0);
+		assert_eq!(analyze("1234").trill_rows, 0);
+	}
+}
@@ -0,0 +1,193 @@
+//! Renders patterns as an animated PNG of notes scrolling towards the receptors
+
+use super::*;
+
+pub struct AnimationRecipe<'a> {
+	pub noteskin: &'a Noteskin,
+	pub scroll_direction: etterna::ScrollDirection,
+	pub keymode: usize,
+	/// Scroll speed. At 1.0, 16th notes are one sprite apart, like in a static pattern image
+	pub vertical_spacing_multiplier: f32,
+	pub bpm: f32,
+	pub frames_per_second: u32,
+	/// Height of the visible playfield in pixels
+	pub screen_height: usize,
+	// List of pattern segments and their snap
+	pub pattern: &'a [(Pattern, FractionalSnap)],
+	/// Limit for the dimensions of a single frame
+	pub max_image_dimensions: (usize, usize),
+	/// Limit for the number of sprites visible in a single frame
+	pub max_sprites: usize,
+	/// Limit for the number of sprites drawn, summed over all frames
+	pub max_total_sprites: usize,
+	/// Limit for the number of pixels, summed over all frames
+	pub max_total_pixels: usize,
+	/// Limit for the number of pixels of the strip that all notes are rendered onto once
+	pub max_strip_pixels: usize,
+	/// Limit for the size of the encoded file in bytes, e.g. Discord's attachment size limit
+	pub max_file_size: usize,
+	pub note_coloring: NoteColoring,
+}
+
+/// Returns the bytes of an APNG file which loops the pattern scrolling by at the given BPM. The
+/// first frame shows the first row at the far end of the screen, the last frame shows the last
+/// row having scrolled past the receptors
+pub fn animate_pattern(recipe: AnimationRecipe<'_>) -> Result<Vec<u8>, Error> {
+	let AnimationRecipe {
+		noteskin,
+		scroll_direction,
+		keymode,
+		vertical_spacing_multiplier,
+		bpm,
+		frames_per_second,
+		screen_height,
+		pattern,
+		max_image_dimensions: (max_width, max_height),
+		max_sprites,
+		max_total_sprites,
+		max_total_pixels,
+		max_strip_pixels,
+		max_file_size,
+		note_coloring,
+	} = recipe;
+
+	let sprite_res = noteskin.sprite_resolution();
+	let width = sprite_res * keymode;
+	let height = screen_height.max(sprite_res);
+	if width > max_width || height > max_height {
+		return Err(Error::ImageTooLarge {
+			width,
+			height,
+			max_width,
+			max_height,
+		});
+	}
+
+	let placed_pattern = place_notes(pattern);
+	let highest_row = placed_pattern.highest_row();
+	let sprites = note_sprites(
+		noteskin,
+		scroll_direction,
+		keymode,
+		&placed_pattern,
+		note_coloring,
+	)?;
+
+	// 48 192nd rows make up a beat
+	let pixels_per_beat = 4.0 * sprite_res as f32 * vertical_spacing_multiplier;
+	let pixels_per_row = pixels_per_beat / 48.0;
+	let pixels_per_frame = pixels_per_beat * bpm / 60.0 / frames_per_second as f32;
+	let y_pos_to_pixels = |y_pos: usize| (y_pos as f32 * pixels_per_row) as i64;
+
+	// Scroll position in pixels: how far the notes have moved towards the receptors. Starts
+	// with the first row entering the screen and ends when the last row has fully passed the
+	// receptors
+	let first_scroll_position = -((height - sprite_res) as f32);
+	let last_scroll_position = highest_row as f32 * pixels_per_row + sprite_res as f32;
+	let frame_count = ((last_scroll_position - first_scroll_position) / pixels_per_frame)
+		.ceil()
+		.max(0.0) as usize
+		+ 1;
+	if frame_count * width * height > max_total_pixels {
+		return Err(Error::AnimationTooLong {
+			frame_count,
+			max_total_pixels,
+		});
+	}
+
+	// The vertical offset at which the note strip is drawn onto each frame
+	let receptor_y = match scroll_direction {
+		etterna::ScrollDirection::Upscroll => 0,
+		etterna::ScrollDirection::Downscroll => (height - sprite_res) as i64,
+	};
+	let strip_offsets = (0..frame_count)
+		.map(|frame_i| {
+			let scroll_position =
+				(first_scroll_position + frame_i as f32 * pixels_per_frame) as i64;
+			match scroll_direction {
+				etterna::ScrollDirection::Upscroll => receptor_y - scroll_position,
+				etterna::ScrollDirection::Downscroll => {
+					receptor_y + scroll_position - y_pos_to_pixels(highest_row)
+				}
+			}
+		})
+		.collect::<Vec<_>>();
+
+	// Check the sprite limits before doing any rendering work
+	let sprite_spans = sprites
+		.iter()
+		.map(|sprite| {
+			let end_y_pos = sprite.stretch_to_y_pos.unwrap_or(sprite.y_pos);
+			(
+				y_pos_to_pixels(sprite.y_pos.min(end_y_pos)),
+				y_pos_to_pixels(sprite.y_pos.max(end_y_pos)) + sprite_res as i64,
+			)
+		})
+		.collect::<Vec<_>>();
+	let mut total_sprite_count = 0;
+	for &strip_offset in &strip_offsets {
+		let visible_sprite_count = keymode
+			+ sprite_spans
+				.iter()
+				.filter(|&&(top, bottom)| {
+					bottom + strip_offset > 0 && top + strip_offset < height as i64
+				})
+				.count();
+		if visible_sprite_count > max_sprites {
+			return Err(Error::TooManySprites {
+				count: visible_sprite_count,
+				limit: max_sprites,
+			});
+		}
+		total_sprite_count += visible_sprite_count;
+	}
+	if total_sprite_count > max_total_sprites {
+		return Err(Error::TooManySprites {
+			count: total_sprite_count,
+			limit: max_total_sprites,
+		});
+	}
+
+	// All notes are rendered once onto a tall strip which is then moved across the frames. The
+	// strip size is checked before anything is allocated
+	let strip = render_sprite_map(
+		SpriteMap {
+			sprites,
+			sprite_resolution: sprite_res,
+			vertical_spacing_multiplier: pixels_per_row / sprite_res as f32,
+		},
+		(width, max_strip_pixels / width),
+	)?;
+
+	let mut apng_bytes = Vec::new();
+	let mut encoder = png::Encoder::new(&mut apng_bytes, width as u32, height as u32);
+	encoder.set_color(png::ColorType::Rgba);
+	encoder.set_depth(png::BitDepth::Eight);
+	encoder.set_animated(frame_count as u32, 0)?;
+	encoder.set_frame_delay(1, frames_per_second as u16)?;
+	let mut writer = encoder.write_header()?;
+
+	for strip_offset in strip_offsets {
+		let mut frame = image::RgbaImage::new(width as u32, height as u32);
+		for lane in 0..keymode {
+			image::imageops::overlay(
+				&mut frame,
+				noteskin.receptor(lane, keymode)?,
+				(lane * sprite_res) as i64,
+				receptor_y,
+			);
+		}
+		image::imageops::overlay(&mut frame, &strip, 0, strip_offset);
+
+		writer.write_image_data(frame.as_raw())?;
+	}
+	writer.finish()?;
+
+	if apng_bytes.len() > max_file_size {
+		return Err(Error::AnimationFileTooLarge {
+			size: apng_bytes.len(),
+			limit: max_file_size,
+		});
+	}
+	Ok(apng_bytes)
+}
@@ -0,0 +1,156 @@
+//! Renders several patterns next to each other for comparison
+
+use super::*;
+
+pub struct ComparedPattern<'a> {
+	pub keymode: usize,
+	pub vertical_spacing_multiplier: f32,
+	// List of pattern segments and their snap
+	pub pattern: &'a [(Pattern, FractionalSnap)],
+	/// Drawn below the pattern. Cut off if it's wider than the pattern
+	pub caption: &'a str,
+}
+
+pub struct ComparisonRecipe<'a> {
+	pub noteskin: &'a Noteskin,
+	pub scroll_direction: etterna::ScrollDirection,
+	pub patterns: &'a [ComparedPattern<'a>],
+	/// Horizontal space between two patterns in pixels
+	pub gap: usize,
+	/// Limit for the dimensions of the combined image
+	pub max_image_dimensions: (usize, usize),
+	/// Limit for the number of sprites, summed over all patterns
+	pub max_sprites: usize,
+	pub overlays: Overlays,
+	pub note_coloring: NoteColoring,
+}
+
+/// Vertical space below each pattern that the caption takes up, in pixels
+pub(crate) fn caption_height(noteskin: &Noteskin) -> usize {
+	((GLYPH_HEIGHT + 2) * caption_scale(noteskin)) as usize
+}
+
+fn caption_scale(noteskin: &Noteskin) -> u32 {
+	(noteskin.sprite_resolution() as u32 / 16).max(1)
+}
+
+/// Shortens the caption with `...` until it fits into the given width
+fn fit_caption(caption: &str, max_width: u32, scale: u32) -> String {
+	if text_width(caption, scale) <= max_width {
+		return caption.to_owned();
+	}
+
+	let mut caption = caption.chars().collect::<Vec<_>>();
+	while !caption.is_empty() && (caption.len() + 3) as u32 * GLYPH_WIDTH * scale > max_width {
+		caption.pop();
+	}
+	caption.into_iter().chain("...".chars()).collect()
+}
+
+/// Draws each pattern like [`draw_pattern`] and places them side by side, with the receptors at
+/// the same height and a caption below each
+pub fn draw_pattern_comparison(recipe: ComparisonRecipe<'_>) -> Result<image::RgbaImage, Error> {
+	let ComparisonRecipe {
+		noteskin,
+		scroll_direction,
+		patterns,
+		gap,
+		max_image_dimensions: (max_width, max_height),
+		max_sprites,
+		overlays,
+		note_coloring,
+	} = recipe;
+
+	let scale = caption_scale(noteskin);
+	let caption_height = caption_height(noteskin);
+
+	let mut images = Vec::new();
+	let mut used_width = 0;
+	let mut used_sprites = 0;
+	for compared_pattern in patterns {
+		if !images.is_empty() {
+			used_width += gap;
+		}
+
+		let max_pattern_dimensions = (
+			max_width.saturating_sub(used_width),
+			max_height.saturating_sub(caption_height),
+		);
+		let image = draw_pattern(PatternRecipe {
+			noteskin,
+			scroll_direction,
+			keymode: compared_pattern.keymode,
+			vertical_spacing_multiplier: compared_pattern.vertical_spacing_multiplier,
+			pattern: compared_pattern.pattern,
+			max_image_dimensions: max_pattern_dimensions,
+			max_sprites: max_sprites.saturating_sub(used_sprites),
+			overlays,
+			note_coloring,
+		})
+		// Report the limits of the whole image, not of this single pattern
+		.map_err(|e| match e {
+			Error::ImageTooLarge { width, height, .. } => Error::ImageTooLarge {
+				width: used_width + width,
+				height: height + caption_height,
+				max_width,
+				max_height,
+			},
+			Error::TooManySprites { count, .. } => Error::TooManySprites {
+				count: used_sprites + count,
+				limit: max_sprites,
+			},
+			other => other,
+		})?;
+
+		used_width += image.width() as usize;
+		used_sprites += count_sprites(noteskin, scroll_direction, compared_pattern, note_coloring)?;
+		images.push(image);
+	}
+
+	let pattern_height = images.iter().map(|image| image.height()).max().unwrap_or(0);
+	let mut canvas =
+		image::RgbaImage::new(used_width as u32, pattern_height + caption_height as u32);
+
+	let mut x = 0;
+	for (image, compared_pattern) in images.iter().zip(patterns) {
+		// Receptors are at the top in upscroll and at the bottom in downscroll
+		let y = match scroll_direction {
+			etterna::ScrollDirection::Upscroll => 0,
+			etterna::ScrollDirection::Downscroll => pattern_height - image.height(),
+		};
+		image::imageops::overlay(&mut canvas, image, x as i64, y as i64);
+
+		let caption = fit_caption(compared_pattern.caption, image.width(), scale);
+		// Even "..." can be wider than very narrow patterns
+		let caption_x = x + image.width().saturating_sub(text_width(&caption, scale)) / 2;
+		draw_text(
+			&mut canvas,
+			(caption_x as i32, (pattern_height + scale) as i32),
+			&caption,
+			scale,
+			TEXT_COLOR,
+		);
+
+		x += image.width() + gap as u32;
+	}
+
+	Ok(canvas)
+}
+
+/// Number of sprites that [`draw_pattern`] draws for this pattern
+fn count_sprites(
+	noteskin: &Noteskin,
+	scroll_direction: etterna::ScrollDirection,
+	compared_pattern: &ComparedPattern<'_>,
+	note_coloring: NoteColoring,
+) -> Result<usize, Error> {
+	let placed_pattern = place_notes(compared_pattern.pattern);
+	let note_sprites = note_sprites(
+		noteskin,
+		scroll_direction,
+		compared_pattern.keymode,
+		&placed_pattern,
+		note_coloring,
+	)?;
+	Ok(compared_pattern.keymode + note_sprites.len())
+}
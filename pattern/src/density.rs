@@ -0,0 +1,145 @@
+//! Notes per second over the course of a pattern, like the density graphs of chart editors
+
+use super::*;
+
+/// Positions of [`PlacedNote`] are in 192nds, so there are 48 of them per beat
+const ROWS_PER_BEAT: f32 = 48.0;
+/// Width of the window in which notes are counted for each point of the graph. Patterns shorter
+/// than that are counted over their whole length
+const WINDOW_SECONDS: f32 = 1.0;
+const NUM_SAMPLES: usize = 200;
+/// In the chord-weighted series, every note of a chord after the first counts only this much.
+/// Hitting a jump is easier than hitting two single notes in the same time
+const CHORD_NOTE_WEIGHT: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DensityPoint {
+	/// Seconds since the first note
+	pub time: f32,
+	/// Notes per second
+	pub nps: f32,
+	/// Like `nps`, but chords count less than their number of notes
+	pub chord_weighted_nps: f32,
+	/// Notes per second in the left half of the lanes. In odd keymodes, the middle lane counts
+	/// half for each hand
+	pub left_hand_nps: f32,
+	pub right_hand_nps: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternDensity {
+	pub points: Vec<DensityPoint>,
+	/// Seconds from the first note to the last
+	pub duration: f32,
+	/// Number of notes divided by the duration, like Etterna's NPS
+	pub average_nps: f32,
+	/// Seconds over which the notes are counted for each point
+	pub window: f32,
+}
+
+impl PatternDensity {
+	pub fn peak_nps(&self) -> f32 {
+		self.points.iter().map(|p| p.nps).fold(0.0, f32::max)
+	}
+}
+
+/// Counts the notes per second around evenly spaced points in time. Mines and fakes don't count
+pub fn pattern_density(
+	pattern: &[(Pattern, FractionalSnap)],
+	keymode: usize,
+	bpm: f32,
+) -> Result<PatternDensity, Error> {
+	let seconds_per_row = 60.0 / bpm / ROWS_PER_BEAT;
+	let rows = note_rows(pattern, keymode)?;
+	// Leading empty rows or mines don't count towards the length
+	let first_position = match rows.first() {
+		Some(&(position, _)) => position,
+		None => return Err(Error::EmptyPattern),
+	};
+	let rows = rows
+		.into_iter()
+		.map(|(position, lanes)| ((position - first_position) as f32 * seconds_per_row, lanes))
+		.collect::<Vec<_>>();
+
+	let duration = match rows.last() {
+		Some(&(time, _)) if time > 0.0 => time,
+		_ => return Err(Error::PatternTooShort),
+	};
+	let window = WINDOW_SECONDS.min(duration);
+
+	let hand_weights = |lane: usize| {
+		if keymode % 2 == 1 && lane == keymode / 2 {
+			(0.5, 0.5)
+		} else if lane < keymode / 2 {
+			(1.0, 0.0)
+		} else {
+			(0.0, 1.0)
+		}
+	};
+
+	let points = (0..NUM_SAMPLES)
+		.map(|i| {
+			let time = duration * i as f32 / (NUM_SAMPLES - 1) as f32;
+			// Near the start and end, part of the window lies outside of the pattern and the
+			// density drops off. Cutting the window off there instead would make the few notes
+			// in it look like spikes
+			let (start, end) = (time - window / 2.0, time + window / 2.0);
+
+			let mut point = DensityPoint {
+				time,
+				nps: 0.0,
+				chord_weighted_nps: 0.0,
+				left_hand_nps: 0.0,
+				right_hand_nps: 0.0,
+			};
+			for (_, lanes) in rows.iter().filter(|&&(t, _)| t >= start && t < end) {
+				point.nps += lanes.len() as f32;
+				point.chord_weighted_nps += 1.0 + (lanes.len() - 1) as f32 * CHORD_NOTE_WEIGHT;
+				for &lane in lanes {
+					let (left, right) = hand_weights(lane);
+					point.left_hand_nps += left;
+					point.right_hand_nps += right;
+				}
+			}
+
+			point.nps /= window;
+			point.chord_weighted_nps /= window;
+			point.left_hand_nps /= window;
+			point.right_hand_nps /= window;
+			point
+		})
+		.collect();
+
+	let num_notes = rows.iter().map(|(_, lanes)| lanes.len()).sum::<usize>();
+	Ok(PatternDensity {
+		points,
+		duration,
+		average_nps: num_notes as f32 / duration,
+		window,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn density(pattern: &str) -> Result<PatternDensity, Error> {
+		let pattern = parse_pattern(pattern, 1000).unwrap();
+		let snap = FractionalSnap::from_snap_number(4).unwrap();
+		pattern_density(&[(pattern, snap)], 4, 60.0)
+	}
+
+	#[test]
+	fn duration_starts_at_first_note() {
+		let density = density("0 0 0 1 2 3 4").unwrap();
+		assert_eq!(density.duration, 3.0);
+		assert_eq!(density.average_nps, 4.0 / 3.0);
+		assert_eq!(density.points[0].time, 0.0);
+	}
+
+	#[test]
+	fn single_row_is_too_short() {
+		assert!(matches!(density("0 0 [12]"), Err(Error::PatternTooShort)));
+		assert!(matches!(density("0 m1"), Err(Error::EmptyPattern)));
+	}
+}
@@ -0,0 +1,95 @@
+//! Converts patterns back into StepMania note data
+
+use super::*;
+
+/// Row counts that a .sm measure may have, from coarse to fine
+const MEASURE_ROW_COUNTS: &[usize] = &[4, 8, 12, 16, 24, 32, 48, 64, 96, 192];
+
+/// Converts pattern segments into the measures of a .sm/.ssc `#NOTES` block. Each measure is
+/// written with the fewest rows that can represent all of its notes
+pub fn pattern_to_sm(
+	pattern: &[(Pattern, FractionalSnap)],
+	keymode: usize,
+) -> Result<String, Error> {
+	let placed_pattern = place_notes(pattern);
+	if placed_pattern.notes.is_empty() {
+		return Err(Error::EmptyPattern);
+	}
+
+	// A hold of length 1 ends on its own row, but StepMania needs the tail after the head. Moving
+	// it one row of the note's snap further keeps the measure as coarse as possible
+	let tail_row = |note: &PlacedNote| {
+		let min_tail_row = note.row + note.snap.iter_192nd_intervals().next_interval() as usize;
+		note.end_row.map(|end_row| end_row.max(min_tail_row))
+	};
+
+	let highest_row = placed_pattern
+		.notes
+		.iter()
+		.filter_map(tail_row)
+		.fold(placed_pattern.highest_row(), usize::max);
+	let num_measures = highest_row / 192 + 1;
+	let mut grid = vec![vec!['0'; keymode]; num_measures * 192];
+	for note in &placed_pattern.notes {
+		let lane = note.lane.column_number_with_keymode(keymode as u32) as usize;
+		if lane >= keymode {
+			return Err(Error::InvalidLaneForKeymode {
+				human_readable_lane: lane + 1,
+				keymode,
+			});
+		}
+
+		grid[note.row][lane] = match note.note_type {
+			NoteType::Tap => '1',
+			NoteType::Mine => 'M',
+			NoteType::Lift => 'L',
+			NoteType::Fake => 'F',
+			NoteType::Hold { .. } => '2',
+			NoteType::Roll { .. } => '4',
+		};
+		if let Some(tail_row) = tail_row(note) {
+			grid[tail_row][lane] = '3';
+		}
+	}
+
+	let mut sm = String::new();
+	for (i, measure) in grid.chunks(192).enumerate() {
+		if i > 0 {
+			sm += ",\n";
+		}
+
+		let is_empty = |row: &Vec<char>| row.iter().all(|&c| c == '0');
+		// UNWRAP: 192 rows can always represent every note
+		let row_count = *MEASURE_ROW_COUNTS
+			.iter()
+			.find(|&&row_count| {
+				let step = 192 / row_count;
+				measure
+					.iter()
+					.enumerate()
+					.all(|(row_i, row)| row_i % step == 0 || is_empty(row))
+			})
+			.unwrap();
+
+		for row in measure.iter().step_by(192 / row_count) {
+			sm.extend(row);
+			sm.push('\n');
+		}
+	}
+	sm += ";\n";
+
+	Ok(sm)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hold_of_length_one_keeps_its_head() {
+		let pattern = parse_pattern("1x1 2", 100).unwrap();
+		let snap = FractionalSnap::from_snap_number(16).unwrap();
+		let sm = pattern_to_sm(&[(pattern, snap)], 4).unwrap();
+		assert_eq!(sm, format!("2000\n3100\n{};\n", "0000\n".repeat(14)));
+	}
+}
@@ -1,4 +1,4 @@
-#[derive(Copy, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
 pub struct FractionalSnap {
 	snap_number: std::num::NonZeroU32,
 }
@@ -15,6 +15,18 @@ impl FractionalSnap {
 		self.snap_number.get()
 	}
 
+	/// Snap of a note at the given position in 192nd steps. Unlike [`etterna::Snap::from_row`],
+	/// 96ths are kept apart from 192nds
+	pub fn from_row(row: usize) -> Self {
+		let snap_number = [4, 8, 12, 16, 24, 32, 48, 64, 96]
+			.iter()
+			.copied()
+			.find(|&snap_number| row % (192 / snap_number) == 0)
+			.unwrap_or(192);
+		// UNWRAP: all candidates are above 0
+		Self::from_snap_number(snap_number as u32).unwrap()
+	}
+
 	pub fn iter_192nd_intervals(&self) -> Iter192ndIntervals {
 		Iter192ndIntervals {
 			exact_192nd_interval: 192.0 / self.snap_number.get() as f32,
@@ -1,6 +1,12 @@
 mod parse;
 pub use parse::*;
 
+mod parse_sm;
+pub use parse_sm::*;
+
+mod simfile;
+pub use simfile::*;
+
 mod noteskin;
 pub use noteskin::*;
 
@@ -10,9 +16,45 @@ pub use fractional_snap::*;
 mod render;
 pub use render::*;
 
+mod animate;
+pub use animate::*;
+
+mod placement;
+pub use placement::*;
+
+mod overlay;
+pub use overlay::*;
+
+mod compose;
+pub use compose::*;
+
+mod svg;
+pub use svg::*;
+
+mod wrap;
+pub use wrap::*;
+
+mod text;
+use text::*;
+
+mod export;
+pub use export::*;
+
+mod analysis;
+pub use analysis::*;
+
+mod density;
+pub use density::*;
+
+mod transform;
+pub use transform::*;
+
 mod structures;
 pub use structures::*;
 
+mod quantize;
+pub use quantize::*;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
 	#[error("Given pattern is empty")]
@@ -28,6 +70,8 @@ pub enum Error {
 	},
 	#[error("Noteskin's texture map doesn't contain all required textures")]
 	NoteskinTextureMapTooSmall,
+	#[error("Row `{row}` doesn't have {expected} columns like the rows before")]
+	InconsistentRowWidth { row: String, expected: usize },
 	#[error("{count} sprites would need to be rendered for this pattern, which exceeds the limit of {limit}")]
 	TooManySprites { count: usize, limit: usize },
 	#[error("Rendered pattern would exceed the limit of {max_width}x{max_height}")]
@@ -37,8 +81,29 @@ pub enum Error {
 		max_width: usize,
 		max_height: usize,
 	},
-	#[error("Holds are not supported yet")]
-	HoldsAreUnsupported,
+	#[error("Animation would need {frame_count} frames, which exceeds the limit of {max_total_pixels} pixels in total")]
+	AnimationTooLong {
+		frame_count: usize,
+		max_total_pixels: usize,
+	},
+	#[error("Animation would be {:.1} MB, which exceeds the limit of {:.1} MB", *.size as f32 / 1e6, *.limit as f32 / 1e6)]
+	AnimationFileTooLarge { size: usize, limit: usize },
+	#[error("Error while encoding the animation or image")]
+	AnimationEncodingError(#[from] png::EncodingError),
+	#[error("`{note}` at column {} is not a valid note", .position + 1)]
+	InvalidNote { note: String, position: usize },
+	#[error("`{delimiter}` at column {} is never closed", .position + 1)]
+	UnclosedDelimiter { delimiter: char, position: usize },
+	#[error("Expected a number after the `*` at column {}", .position + 1)]
+	InvalidRepetitionCount { position: usize },
+	#[error("Macro `{name}` at column {} is used before it's defined", .position + 1)]
+	UnknownMacro { name: String, position: usize },
+	#[error("Macro `{name}` at column {} must be defined with `<{name}>={{...}}`", .position + 1)]
+	InvalidMacroDefinition { name: String, position: usize },
+	#[error("Pattern would have more than {limit} rows after expanding repetitions and holds")]
+	PatternTooLong { limit: usize },
+	#[error("Pattern is too short to measure its density, it needs notes on at least two rows")]
+	PatternTooShort,
 }
 
 fn trimmed_backtrace() -> String {
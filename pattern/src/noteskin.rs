@@ -47,29 +47,51 @@ fn rotate_clockwise_by(img: &image::RgbaImage, degrees: u32) -> image::RgbaImage
 	)
 }
 
-// The returned index ranges from 0 to 7, so it can be used to index into a [T; 8]
-fn snap_to_texture_index(snap: etterna::Snap) -> usize {
-	match snap {
-		etterna::Snap::_4th => 0,
-		etterna::Snap::_8th => 1,
-		etterna::Snap::_12th => 2,
-		etterna::Snap::_16th => 3,
-		etterna::Snap::_24th => 4,
-		etterna::Snap::_32th => 5,
-		etterna::Snap::_48th => 6,
-		etterna::Snap::_64th => 7,
-		etterna::Snap::_192th => 7,
-	}
+/// Texture maps have one row per snap: 4th, 8th, 12th, 16th, 24th, 32nd, 48th, 64th, and
+/// optionally 192nd, or 96th and 192nd
+const MAX_QUANT_TEXTURES: usize = 10;
+
+/// Picks the row of a texture map with `num_textures` rows for the given snap. Snaps without a
+/// texture of their own use the last one, which is the 192nd texture if the noteskin has one
+fn snap_to_texture_index(snap: super::FractionalSnap, num_textures: usize) -> usize {
+	let last_index = num_textures.saturating_sub(1);
+	let index = match snap.snap_number() {
+		4 => 0,
+		8 => 1,
+		12 => 2,
+		16 => 3,
+		24 => 4,
+		32 => 5,
+		48 => 6,
+		64 => 7,
+		96 if num_textures == MAX_QUANT_TEXTURES => 8,
+		_ => last_index,
+	};
+	index.min(last_index)
 }
 
-fn array_from_iter<const N: usize, T: Default>(mut iter: impl Iterator<Item = T>) -> [T; N] {
-	std::array::from_fn(|_| iter.next().unwrap_or_default())
+/// Grayscale copy of the texture, brightened so that its average pixel is light gray. Tinting it
+/// with a color then yields that color at roughly full brightness
+fn to_tintable(texture: &image::RgbaImage) -> image::RgbaImage {
+	let luminance =
+		|p: &image::Rgba<u8>| 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32;
+	let (luminance_sum, opaque_pixels) = texture
+		.pixels()
+		.filter(|p| p[3] > 0)
+		.fold((0.0, 0), |(sum, count), p| (sum + luminance(p), count + 1));
+	let mean_luminance = (luminance_sum / opaque_pixels.max(1) as f32).max(1.0);
+
+	image::RgbaImage::from_fn(texture.width(), texture.height(), |x, y| {
+		let p = texture.get_pixel(x, y);
+		let gray = (luminance(p) * 200.0 / mean_luminance).min(255.0) as u8;
+		image::Rgba([gray, gray, gray, p[3]])
+	})
 }
 
 enum Textures {
 	LdurWith6k {
 		receptors: [image::RgbaImage; 6],
-		notes: [[image::RgbaImage; 6]; 8], // first four are LDUR, then come left-up and right-up
+		notes: Vec<[image::RgbaImage; 6]>, // first four are LDUR, then come left-up and right-up
 		mine: image::RgbaImage,
 	},
 	MonoSnapLdur {
@@ -79,22 +101,133 @@ enum Textures {
 	},
 	Pump {
 		receptors: [image::RgbaImage; 5],
-		notes: [[image::RgbaImage; 5]; 8],
+		notes: Vec<[image::RgbaImage; 5]>,
 		mine: image::RgbaImage,
 	},
 	Bar {
 		receptor: image::RgbaImage,
-		notes: [image::RgbaImage; 8],
+		notes: Vec<image::RgbaImage>,
 		mine: image::RgbaImage,
 	},
 }
 
+/// Hold and roll textures are shared between all lanes. Bodies are stretched from the center of
+/// the hold head to the tail. Tails are drawn like a note at the hold end, with the end cap in the
+/// lower half of the sprite
+struct HoldTextures {
+	hold_body: image::RgbaImage,
+	hold_tail: image::RgbaImage,
+	roll_body: image::RgbaImage,
+	roll_tail: image::RgbaImage,
+}
+
+impl HoldTextures {
+	/// Plain textures for noteskins that don't come with hold graphics
+	fn fallback(sprite_resolution: usize) -> Self {
+		let res = sprite_resolution as u32;
+		let margin = res / 5;
+		let transparent = image::Rgba([0, 0, 0, 0]);
+		let hold_color = image::Rgba([220, 220, 220, 200]);
+		let roll_color = image::Rgba([230, 190, 60, 200]);
+		let roll_stripe_color = image::Rgba([150, 110, 20, 200]);
+
+		let is_in_body = |x: u32| x >= margin && x < res - margin;
+		let is_in_tail = |x: u32, y: u32| {
+			// upper half continues the body, lower half is a rounded end cap
+			let (dx, dy) = (x as f32 - res as f32 / 2.0, y as f32 - res as f32 / 2.0);
+			let cap_radius = res as f32 / 2.0 - margin as f32;
+			is_in_body(x) && (dy <= 0.0 || dx * dx + dy * dy <= cap_radius * cap_radius)
+		};
+		let roll_color_at = |x: u32, y: u32| {
+			if (x + y) / (res / 4).max(1) % 2 == 0 {
+				roll_color
+			} else {
+				roll_stripe_color
+			}
+		};
+
+		Self {
+			hold_body: image::RgbaImage::from_fn(res, res, |x, _| {
+				if is_in_body(x) {
+					hold_color
+				} else {
+					transparent
+				}
+			}),
+			hold_tail: image::RgbaImage::from_fn(res, res, |x, y| {
+				if is_in_tail(x, y) {
+					hold_color
+				} else {
+					transparent
+				}
+			}),
+			roll_body: image::RgbaImage::from_fn(res, res, |x, y| {
+				if is_in_body(x) {
+					roll_color_at(x, y)
+				} else {
+					transparent
+				}
+			}),
+			roll_tail: image::RgbaImage::from_fn(res, res, |x, y| {
+				if is_in_tail(x, y) {
+					roll_color_at(x, y)
+				} else {
+					transparent
+				}
+			}),
+		}
+	}
+
+	fn for_each_texture(&mut self, mut f: impl FnMut(&mut image::RgbaImage)) {
+		f(&mut self.hold_body);
+		f(&mut self.hold_tail);
+		f(&mut self.roll_body);
+		f(&mut self.roll_tail);
+	}
+}
+
+/// Horizontal bar that is drawn on top of a note to mark it as a lift
+fn generate_lift_marker(sprite_resolution: usize) -> image::RgbaImage {
+	let res = sprite_resolution as u32;
+	image::RgbaImage::from_fn(res, res, |x, y| {
+		if x >= res / 8 && x < res - res / 8 && y >= res * 3 / 4 && y < res * 7 / 8 {
+			image::Rgba([255, 255, 255, 230])
+		} else {
+			image::Rgba([0, 0, 0, 0])
+		}
+	})
+}
+
 pub struct Noteskin {
 	sprite_resolution: usize,
 	textures: Textures,
+	/// Grayscale versions of the 4th notes, indexed like the note textures of a single snap
+	tintable_notes: Vec<image::RgbaImage>,
+	holds: HoldTextures,
+	lift_marker: image::RgbaImage,
 }
 
 impl Noteskin {
+	fn new(sprite_resolution: usize, textures: Textures) -> Self {
+		let tintable_notes = match &textures {
+			Textures::LdurWith6k { notes, .. } => notes.first().map_or(&[][..], |n| &n[..]),
+			Textures::MonoSnapLdur { notes, .. } => &notes[..],
+			Textures::Pump { notes, .. } => notes.first().map_or(&[][..], |n| &n[..]),
+			Textures::Bar { notes, .. } => &notes[..notes.len().min(1)],
+		}
+		.iter()
+		.map(to_tintable)
+		.collect();
+
+		Self {
+			sprite_resolution,
+			textures,
+			tintable_notes,
+			holds: HoldTextures::fallback(sprite_resolution),
+			lift_marker: generate_lift_marker(sprite_resolution),
+		}
+	}
+
 	pub fn read_pump(
 		sprite_resolution: usize,
 		center_notes_path: &str,
@@ -123,20 +256,20 @@ impl Noteskin {
 			]
 		}
 
-		Self {
+		Self::new(
 			sprite_resolution,
-			textures: Textures::Pump {
+			Textures::Pump {
 				receptors: make_note_set(center_receptor, corner_receptor),
-				notes: array_from_iter(
-					Iterator::zip(
-						iterate_center_column_of_texture_map(&center_notes, sprite_resolution),
-						iterate_center_column_of_texture_map(&corner_notes, sprite_resolution),
-					)
-					.map(|(center_note, corner_note)| make_note_set(center_note, corner_note)),
-				),
+				notes: Iterator::zip(
+					iterate_center_column_of_texture_map(&center_notes, sprite_resolution),
+					iterate_center_column_of_texture_map(&corner_notes, sprite_resolution),
+				)
+				.take(MAX_QUANT_TEXTURES)
+				.map(|(center_note, corner_note)| make_note_set(center_note, corner_note))
+				.collect(),
 				mine,
 			},
-		}
+		)
 	}
 
 	pub fn read_ldur_with_6k(
@@ -161,17 +294,17 @@ impl Noteskin {
 			]
 		}
 
-		Self {
+		Self::new(
 			sprite_resolution,
-			textures: Textures::LdurWith6k {
+			Textures::LdurWith6k {
 				receptors: make_note_set(receptor),
-				notes: array_from_iter(
-					iterate_center_column_of_texture_map(&notes, sprite_resolution)
-						.map(make_note_set),
-				),
+				notes: iterate_center_column_of_texture_map(&notes, sprite_resolution)
+					.take(MAX_QUANT_TEXTURES)
+					.map(make_note_set)
+					.collect(),
 				mine,
 			},
-		}
+		)
 	}
 
 	#[allow(clippy::too_many_arguments)] // ehhhhhhh this is fine
@@ -187,9 +320,9 @@ impl Noteskin {
 		right_receptor_path: &str,
 		mine_path: &str,
 	) -> Self {
-		Self {
+		Self::new(
 			sprite_resolution,
-			textures: Textures::MonoSnapLdur {
+			Textures::MonoSnapLdur {
 				notes: [
 					open_image(left_note_path),
 					open_image(down_note_path),
@@ -204,7 +337,7 @@ impl Noteskin {
 				],
 				mine: open_image(mine_path),
 			},
-		}
+		)
 	}
 
 	pub fn read_bar(
@@ -218,17 +351,45 @@ impl Noteskin {
 		let receptor = open_middle_texture(receptor_path);
 		let notes_map = open_image(notes_path);
 
-		Self {
+		Self::new(
 			sprite_resolution,
-			textures: Textures::Bar {
+			Textures::Bar {
 				receptor,
-				notes: array_from_iter(iterate_center_column_of_texture_map(
-					&notes_map,
-					sprite_resolution,
-				)),
+				notes: iterate_center_column_of_texture_map(&notes_map, sprite_resolution)
+					.take(MAX_QUANT_TEXTURES)
+					.collect(),
 				mine,
 			},
-		}
+		)
+	}
+
+	/// Replace the fallback hold and roll textures with the given images. If the noteskin has no
+	/// dedicated roll textures, the hold textures are reused for rolls
+	pub fn read_hold_textures(
+		&mut self,
+		hold_body_path: &str,
+		hold_tail_path: &str,
+		roll_body_path: Option<&str>,
+		roll_tail_path: Option<&str>,
+	) {
+		let res = self.sprite_resolution as u32;
+		let open_resized = |path: &str| {
+			image::imageops::resize(
+				&open_image(path),
+				res,
+				res,
+				image::imageops::FilterType::Triangle,
+			)
+		};
+
+		let hold_body = open_resized(hold_body_path);
+		let hold_tail = open_resized(hold_tail_path);
+		self.holds = HoldTextures {
+			roll_body: roll_body_path.map_or_else(|| hold_body.clone(), open_resized),
+			roll_tail: roll_tail_path.map_or_else(|| hold_tail.clone(), open_resized),
+			hold_body,
+			hold_tail,
+		};
 	}
 
 	fn check_keymode(&self, lane: usize, keymode: usize) -> Result<(), super::Error> {
@@ -278,24 +439,46 @@ impl Noteskin {
 		&self,
 		lane: usize,
 		keymode: usize,
-		snap: etterna::Snap,
+		snap: super::FractionalSnap,
 	) -> Result<&image::RgbaImage, super::Error> {
 		self.check_keymode(lane, keymode)?;
 
+		let texture_map_too_small = super::Error::NoteskinTextureMapTooSmall;
 		Ok(match &self.textures {
 			Textures::LdurWith6k { notes, .. } => {
-				&notes[snap_to_texture_index(snap)][self.lane_to_note_array_index(lane, keymode)?]
+				let snap_notes = notes
+					.get(snap_to_texture_index(snap, notes.len()))
+					.ok_or(texture_map_too_small)?;
+				&snap_notes[self.lane_to_note_array_index(lane, keymode)?]
 			}
 			Textures::MonoSnapLdur { notes, .. } => {
 				&notes[self.lane_to_note_array_index(lane, keymode)?]
 			}
 			Textures::Pump { notes, .. } => {
-				&notes[snap_to_texture_index(snap)][self.lane_to_note_array_index(lane, keymode)?]
+				let snap_notes = notes
+					.get(snap_to_texture_index(snap, notes.len()))
+					.ok_or(texture_map_too_small)?;
+				&snap_notes[self.lane_to_note_array_index(lane, keymode)?]
 			}
-			Textures::Bar { notes, .. } => &notes[snap_to_texture_index(snap)],
+			Textures::Bar { notes, .. } => notes
+				.get(snap_to_texture_index(snap, notes.len()))
+				.ok_or(texture_map_too_small)?,
 		})
 	}
 
+	/// Grayscale note which is meant to be tinted with a snap color, so that any snap can be
+	/// drawn in its own color. The returned image has the resolution NxN, where N can be obtained
+	/// with `sprite_resolution()`
+	pub fn tintable_note(
+		&self,
+		lane: usize,
+		keymode: usize,
+	) -> Result<&image::RgbaImage, super::Error> {
+		self.tintable_notes
+			.get(self.lane_to_note_array_index(lane, keymode)?)
+			.ok_or(super::Error::NoteskinTextureMapTooSmall)
+	}
+
 	/// The returned image has the resolution NxN, where N can be obtained with `sprite_resolution()`
 	pub fn receptor(&self, lane: usize, keymode: usize) -> Result<&image::RgbaImage, super::Error> {
 		self.check_keymode(lane, keymode)?;
@@ -324,6 +507,32 @@ impl Noteskin {
 		})
 	}
 
+	/// The returned image has the resolution NxN, where N can be obtained with `sprite_resolution()`
+	pub fn hold_body(&self) -> &image::RgbaImage {
+		&self.holds.hold_body
+	}
+
+	/// The returned image has the resolution NxN, where N can be obtained with `sprite_resolution()`
+	pub fn hold_tail(&self) -> &image::RgbaImage {
+		&self.holds.hold_tail
+	}
+
+	/// The returned image has the resolution NxN, where N can be obtained with `sprite_resolution()`
+	pub fn roll_body(&self) -> &image::RgbaImage {
+		&self.holds.roll_body
+	}
+
+	/// The returned image has the resolution NxN, where N can be obtained with `sprite_resolution()`
+	pub fn roll_tail(&self) -> &image::RgbaImage {
+		&self.holds.roll_tail
+	}
+
+	/// Drawn on top of a note to mark it as a lift. The returned image has the resolution NxN,
+	/// where N can be obtained with `sprite_resolution()`
+	pub fn lift_marker(&self) -> &image::RgbaImage {
+		&self.lift_marker
+	}
+
 	pub fn sprite_resolution(&self) -> usize {
 		self.sprite_resolution
 	}
@@ -385,10 +594,13 @@ impl Noteskin {
 				f(receptor);
 			}
 		}
+		for note in &mut self.tintable_notes {
+			f(note);
+		}
 	}
 
 	pub fn resize_sprites(&mut self, sprite_resolution: u32) {
-		self.for_each_texture(|texture| {
+		let resize = |texture: &mut image::RgbaImage| {
 			// Triangle (aka bilinear) is the fastest resize algorithm that doesn't look garbage
 			*texture = image::imageops::resize(
 				texture,
@@ -396,10 +608,14 @@ impl Noteskin {
 				sprite_resolution,
 				image::imageops::FilterType::Triangle,
 			);
-		});
+		};
+		self.for_each_texture(resize);
+		self.holds.for_each_texture(resize);
+		resize(&mut self.lift_marker);
 		self.sprite_resolution = sprite_resolution as usize;
 	}
 
+	// Hold textures are left alone because their end caps have a fixed orientation
 	pub fn turn_sprites_upside_down(&mut self) {
 		self.for_each_texture(|texture| image::imageops::rotate180_in_place(texture));
 	}
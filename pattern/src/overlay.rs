@@ -0,0 +1,289 @@
+//! Optional layers drawn around and behind the notes: beat lines, beat numbers, a snap legend,
+//! and lanes tinted by hand with brackets marking jacks
+
+use super::*;
+use imageproc::rect::Rect;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overlays {
+	/// Horizontal lines behind the notes at every beat, brighter at every measure
+	pub beat_lines: bool,
+	/// Beat numbers in a gutter left of the lanes, counting from 0 like in ArrowVortex
+	pub beat_numbers: bool,
+	/// A strip right of the lanes showing the snap of each pattern segment in its color
+	pub snap_legend: bool,
+	/// Lane backgrounds tinted by the hand that plays them, and brackets along jacks and minijacks
+	pub hands: bool,
+}
+
+impl Overlays {
+	pub fn any(&self) -> bool {
+		self.beat_lines || self.beat_numbers || self.snap_legend || self.hands
+	}
+}
+
+const ROWS_PER_BEAT: usize = 48;
+const BEATS_PER_MEASURE: usize = 4;
+/// Labelling beats even more rarely would only leave the first label in any pattern that fits
+/// into an image
+const MAX_BEAT_LABEL_INTERVAL: usize = 1 << 16;
+
+const BEAT_LINE_COLOR: image::Rgba<u8> = image::Rgba([128, 128, 128, 96]);
+const MEASURE_LINE_COLOR: image::Rgba<u8> = image::Rgba([160, 160, 160, 192]);
+
+const LEFT_HAND_COLOR: image::Rgba<u8> = image::Rgba([80, 140, 255, 48]);
+const RIGHT_HAND_COLOR: image::Rgba<u8> = image::Rgba([255, 90, 90, 48]);
+const MINIJACK_BRACKET_COLOR: image::Rgba<u8> = image::Rgba([255, 210, 60, 255]);
+const JACK_BRACKET_COLOR: image::Rgba<u8> = image::Rgba([255, 70, 70, 255]);
+
+/// Approximates the colors that Etterna uses for each snap. Snaps that Etterna doesn't know, like
+/// 20ths, get a color of their own too
+pub fn snap_color(snap: FractionalSnap) -> image::Rgba<u8> {
+	image::Rgba(match snap.snap_number() {
+		4 => [255, 59, 59, 255],
+		8 => [59, 108, 255, 255],
+		12 => [176, 59, 255, 255],
+		16 => [255, 230, 59, 255],
+		24 => [255, 59, 203, 255],
+		32 => [255, 150, 59, 255],
+		48 => [59, 230, 255, 255],
+		64 => [59, 255, 108, 255],
+		96 => [150, 200, 150, 255],
+		192 => [160, 160, 160, 255],
+		// Spread the other snaps around the color wheel so that neighboring snaps differ
+		other => hue_to_color((other * 47 % 360) as f32),
+	})
+}
+
+fn hue_to_color(hue: f32) -> [u8; 4] {
+	let channel = |offset: f32| {
+		let k = (offset + hue / 60.0) % 6.0;
+		let intensity = 1.0 - k.min(4.0 - k).clamp(0.0, 1.0) * 0.75;
+		(intensity * 255.0) as u8
+	};
+	[channel(5.0), channel(3.0), channel(1.0), 255]
+}
+
+pub(crate) struct OverlayLayout<'a> {
+	pub sprite_resolution: usize,
+	pub keymode: usize,
+	/// Same as in [`SpriteMap`]
+	pub vertical_spacing_multiplier: f32,
+	pub scroll_direction: etterna::ScrollDirection,
+	pub highest_row: usize,
+	pub placed_pattern: &'a PlacedPattern,
+	pub pattern: &'a [(Pattern, FractionalSnap)],
+}
+
+/// Finds the runs of consecutive note rows that all hit the given lane. Returns the 192nd positions
+/// of the first and last row of each run, and its length in notes. Single notes aren't included
+fn jacks_in_lane(
+	rows: &[(usize, Vec<usize>)],
+	lane: usize,
+) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+	rows.split(move |(_, lanes)| !lanes.contains(&lane))
+		.filter(|run| run.len() >= 2)
+		.map(|run| (run[0].0, run[run.len() - 1].0, run.len()))
+}
+
+/// Places the rendered notes onto a bigger canvas with the requested overlays
+pub(crate) fn draw_overlays(
+	notes_image: image::RgbaImage,
+	layout: OverlayLayout<'_>,
+	overlays: Overlays,
+	(max_width, max_height): (usize, usize),
+) -> Result<image::RgbaImage, Error> {
+	let OverlayLayout {
+		sprite_resolution,
+		keymode,
+		vertical_spacing_multiplier,
+		scroll_direction,
+		highest_row,
+		placed_pattern,
+		pattern,
+	} = layout;
+	let res = sprite_resolution as u32;
+
+	let gutter_width = if overlays.beat_numbers { res } else { 0 };
+	let legend_width = if overlays.snap_legend { res / 2 } else { 0 };
+	let width = (gutter_width + notes_image.width() + legend_width) as usize;
+	let height = notes_image.height() as usize;
+	if width > max_width || height > max_height {
+		return Err(Error::ImageTooLarge {
+			width,
+			height,
+			max_width,
+			max_height,
+		});
+	}
+	let mut canvas = image::RgbaImage::new(width as u32, height as u32);
+
+	// Vertical pixel position of the center of the notes at the given 192nd row
+	let row_to_center_pixel = |row: usize| {
+		let y_pos = match scroll_direction {
+			etterna::ScrollDirection::Upscroll => row,
+			etterna::ScrollDirection::Downscroll => highest_row - row,
+		};
+		((y_pos * sprite_resolution) as f32 * vertical_spacing_multiplier) as i32 + res as i32 / 2
+	};
+	let beat_spacing = (ROWS_PER_BEAT * sprite_resolution) as f32 * vertical_spacing_multiplier;
+	let beats = (0..=(highest_row / ROWS_PER_BEAT))
+		.map(|beat| (beat, row_to_center_pixel(beat * ROWS_PER_BEAT)));
+
+	// Hand tints go behind everything else
+	if overlays.hands {
+		for lane in 0..keymode {
+			// In odd keymodes, the middle lane may be played by either hand
+			let color = if lane < keymode / 2 {
+				LEFT_HAND_COLOR
+			} else if keymode - 1 - lane < keymode / 2 {
+				RIGHT_HAND_COLOR
+			} else {
+				continue;
+			};
+			imageproc::drawing::draw_filled_rect_mut(
+				&mut canvas,
+				Rect::at((gutter_width + lane as u32 * res) as i32, 0).of_size(res, height as u32),
+				color,
+			);
+		}
+	}
+
+	// Lines go behind the notes
+	if overlays.beat_lines {
+		let thickness = (res / 32).max(1);
+		// Beat lines that are too close together only add noise
+		let draw_beat_lines = beat_spacing >= (4 * thickness) as f32;
+		for (beat, y) in beats.clone() {
+			let (color, thickness) = if beat % BEATS_PER_MEASURE == 0 {
+				(MEASURE_LINE_COLOR, thickness * 2)
+			} else if draw_beat_lines {
+				(BEAT_LINE_COLOR, thickness)
+			} else {
+				continue;
+			};
+			imageproc::drawing::draw_filled_rect_mut(
+				&mut canvas,
+				Rect::at(gutter_width as i32, y - thickness as i32 / 2)
+					.of_size(notes_image.width(), thickness),
+				color,
+			);
+		}
+	}
+
+	image::imageops::overlay(&mut canvas, &notes_image, gutter_width as i64, 0);
+
+	// Brackets go on top of the notes, along the left edge of the lane
+	if overlays.hands {
+		let thickness = (res / 16).max(1);
+		let tick_length = res / 6;
+		let rows = note_rows(pattern, keymode)?;
+		for lane in 0..keymode {
+			for (first_row, last_row, length) in jacks_in_lane(&rows, lane) {
+				let color = if length == 2 {
+					MINIJACK_BRACKET_COLOR
+				} else {
+					JACK_BRACKET_COLOR
+				};
+				let (first_y, last_y) = (
+					row_to_center_pixel(first_row),
+					row_to_center_pixel(last_row),
+				);
+				let (top, bottom) = (first_y.min(last_y), first_y.max(last_y));
+				let x = (gutter_width + lane as u32 * res + thickness) as i32;
+				imageproc::drawing::draw_filled_rect_mut(
+					&mut canvas,
+					Rect::at(x, top).of_size(thickness, (bottom - top) as u32 + thickness),
+					color,
+				);
+				for y in [top, bottom] {
+					imageproc::drawing::draw_filled_rect_mut(
+						&mut canvas,
+						Rect::at(x, y).of_size(tick_length, thickness),
+						color,
+					);
+				}
+			}
+		}
+	}
+
+	// Without any spacing between beats, the labels would overlap no matter how many are skipped
+	if overlays.beat_numbers && beat_spacing > 0.0 {
+		let scale = (res / 16).max(1);
+		// Label every beat, or every second, fourth... beat if they would overlap otherwise
+		let mut label_every = 1;
+		while beat_spacing * (label_every as f32) < ((GLYPH_HEIGHT + 1) * scale) as f32
+			&& label_every < MAX_BEAT_LABEL_INTERVAL
+		{
+			label_every *= 2;
+		}
+		for (beat, y) in beats.step_by(label_every) {
+			let x = gutter_width as i32 - text_width(&beat.to_string(), scale) as i32;
+			draw_text(
+				&mut canvas,
+				(x, y - (GLYPH_HEIGHT * scale / 2) as i32),
+				&beat.to_string(),
+				scale,
+				TEXT_COLOR,
+			);
+		}
+	}
+
+	if overlays.snap_legend {
+		let x = (gutter_width + notes_image.width()) as i32;
+		let scale = (res / 32).max(1);
+		let segments = placed_pattern
+			.segment_starts
+			.iter()
+			.zip(pattern)
+			// empty segments don't show up in the image
+			.filter(|(_, (pattern, _))| !pattern.rows.is_empty())
+			.map(|(&start, &(_, snap))| (start, snap))
+			.collect::<Vec<_>>();
+		for (i, &(start, snap)) in segments.iter().enumerate() {
+			// The first segment begins at the edge of the image, the others in the middle of
+			// their first row
+			let start_pixel = if i == 0 {
+				match scroll_direction {
+					etterna::ScrollDirection::Upscroll => 0,
+					etterna::ScrollDirection::Downscroll => height as i32,
+				}
+			} else {
+				row_to_center_pixel(start)
+			};
+			let end_pixel = match segments.get(i + 1) {
+				Some(&(next_start, _)) => row_to_center_pixel(next_start),
+				None => match scroll_direction {
+					etterna::ScrollDirection::Upscroll => height as i32,
+					etterna::ScrollDirection::Downscroll => 0,
+				},
+			};
+			let (top, bottom) = (start_pixel.min(end_pixel), start_pixel.max(end_pixel));
+			if bottom <= top {
+				continue;
+			}
+
+			imageproc::drawing::draw_filled_rect_mut(
+				&mut canvas,
+				Rect::at(x, top).of_size(legend_width, (bottom - top) as u32),
+				snap_color(snap),
+			);
+			// Label the segment at the side where it begins
+			let label_y = match scroll_direction {
+				etterna::ScrollDirection::Upscroll => top + scale as i32,
+				etterna::ScrollDirection::Downscroll => {
+					bottom - ((GLYPH_HEIGHT + 1) * scale) as i32
+				}
+			};
+			draw_text(
+				&mut canvas,
+				(x + scale as i32, label_y),
+				&snap.snap_number().to_string(),
+				scale,
+				image::Rgba([0, 0, 0, 255]),
+			);
+		}
+	}
+
+	Ok(canvas)
+}
@@ -1,4 +1,4 @@
-use super::structures;
+use super::{structures, Error};
 
 /// GENERIC PARSING UTILITES
 
@@ -44,14 +44,14 @@ fn pop_literal<'a>(t: &'a str, s: &str) -> Option<&'a str> {
 /// INTERNAL PATTERN STRUCTURES
 
 /// Represents a note pattern without snap changes.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Row {
 	notes: Vec<(Lane, NoteType)>,
 	// this field is a difference in this internal representation
 	move_draw_cursor_to_end_of_holds: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 enum Lane {
 	Index(u32),
 	Left,
@@ -64,142 +64,539 @@ enum Lane {
 
 use structures::NoteType; // we can use that struct internally as is no modifications needed
 
+type Note = (Lane, NoteType);
+
 /// PARSING CODE
 
+/// Position of `t` within `input` in characters, for error messages. `t` must be a substring of
+/// `input`
+fn position(input: &str, t: &str) -> usize {
+	let byte_offset = t.as_ptr() as usize - input.as_ptr() as usize;
+	input[..byte_offset].chars().count()
+}
+
 /// Pop a number, either single digit `8...` or multidigit `(16)...`
 fn parse_number(t: &str) -> Option<(&str, Option<u32>)> {
 	let (t, number) = pop_delimited(t, "(", ")").or_else(|| pop_char_str(t))?;
 	Some((t, number.parse().ok()))
 }
 
-/// Option<Lane> is None for invalid char or empty ("0")
+/// Option<Lane> is None for invalid char
 fn parse_tap(t: &str) -> Option<(&str, Option<Lane>)> {
-	if let Some((t, num)) = parse_number(t) {
-		let lane = num.map(|num| num.checked_sub(1).map_or(Lane::Empty, Lane::Index));
-		Some((t, lane))
-	} else {
-		let (t, char_) = pop_char(t)?;
-		let lane = match char_.to_ascii_lowercase() {
-			'l' => Some(Lane::Left),
-			'd' => Some(Lane::Down),
-			'u' => Some(Lane::Up),
-			'r' => Some(Lane::Right),
-			_ => None,
-		};
-		Some((t, lane))
-	}
+	let (rest, char_) = pop_char(t)?;
+	let lane = match char_.to_ascii_lowercase() {
+		'l' => Lane::Left,
+		'd' => Lane::Down,
+		'u' => Lane::Up,
+		'r' => Lane::Right,
+		_ => {
+			let (t, num) = parse_number(t)?;
+			let lane = num.map(|num| num.checked_sub(1).map_or(Lane::Empty, Lane::Index));
+			return Some((t, lane));
+		}
+	};
+	Some((rest, Some(lane)))
 }
 
-fn parse_note(mut t: &str) -> Option<(&str, Option<(Lane, NoteType)>)> {
-	let mut note_type = NoteType::Tap;
+/// Whether the delimited part at the start of `t`, which was parsed up to `rest`, is missing its
+/// closing delimiter
+fn is_unterminated(t: &str, rest: &str, start: &str, end: &str) -> bool {
+	t.starts_with(start) && !t[..(t.len() - rest.len())].ends_with(end)
+}
 
-	// If prefixed with 'm', change to mine
-	if let Some(new_t) = pop_literal(t, "m") {
-		t = new_t;
-		note_type = NoteType::Mine;
+fn to_structures_lane(lane: Lane) -> Option<structures::Lane> {
+	match lane {
+		Lane::Index(num) => Some(structures::Lane::Index(num)),
+		Lane::Left => Some(structures::Lane::Left),
+		Lane::Down => Some(structures::Lane::Down),
+		Lane::Up => Some(structures::Lane::Up),
+		Lane::Right => Some(structures::Lane::Right),
+		Lane::Empty => None,
 	}
+}
 
-	let (mut t, lane) = parse_tap(t)?;
+/// A problem in a pattern string that the parser could recover from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+	/// Text that isn't part of the pattern syntax. It was skipped
+	UnknownCharacter { text: String, position: usize },
+	/// A `[`, `(` or `{` that is never closed. It was treated as closed at the end of the input
+	UnterminatedBracket { bracket: char, position: usize },
+	/// A note in a lane that doesn't exist in the keymode
+	LaneExceedsKeymode {
+		human_readable_lane: usize,
+		keymode: usize,
+		position: usize,
+	},
+}
+
+impl ParseWarning {
+	/// Where in the pattern string the problem is, in characters
+	pub fn position(&self) -> usize {
+		match *self {
+			Self::UnknownCharacter { position, .. } => position,
+			Self::UnterminatedBracket { position, .. } => position,
+			Self::LaneExceedsKeymode { position, .. } => position,
+		}
+	}
 
-	// If postfixed by `x<number>`, change to hold
-	if let Some((new_t, Some(length))) = pop_literal(t, "x").and_then(parse_number) {
-		t = new_t;
-		note_type = NoteType::Hold { length };
+	fn into_error(self) -> Error {
+		match self {
+			Self::UnknownCharacter { text, position } => Error::InvalidNote {
+				note: text,
+				position,
+			},
+			Self::UnterminatedBracket { bracket, position } => Error::UnclosedDelimiter {
+				delimiter: bracket,
+				position,
+			},
+			Self::LaneExceedsKeymode {
+				human_readable_lane,
+				keymode,
+				..
+			} => Error::InvalidLaneForKeymode {
+				human_readable_lane,
+				keymode,
+			},
+		}
 	}
+}
 
-	let note = lane.map(|lane| (lane, note_type));
-	Some((t, note))
+/// Doesn't include the position, see [`ParseWarning::position`]
+impl std::fmt::Display for ParseWarning {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::UnknownCharacter { text, .. } => write!(f, "Unknown character `{}`", text),
+			Self::UnterminatedBracket { bracket, .. } => write!(f, "`{}` is never closed", bracket),
+			Self::LaneExceedsKeymode {
+				human_readable_lane,
+				keymode,
+				..
+			} => write!(
+				f,
+				"Lane {} doesn't exist in {}k",
+				human_readable_lane, keymode
+			),
+		}
+	}
 }
 
-fn pop_until_none<'a, T: 'a>(
-	mut t: &'a str,
-	f: fn(&str) -> Option<(&str, T)>,
-) -> impl Iterator<Item = T> + 'a {
-	std::iter::from_fn(move || {
-		let (new_t, data) = f(t)?;
-		t = new_t;
-		Some(data)
-	})
+/// Parses pattern strings. On top of single notes and chords, the syntax supports repetition
+/// groups like `{[12][34]}*16` and macros, which are defined with `<name>={...}` and used with
+/// `<name>` or `<name>*4`.
+///
+/// Macros stay defined for the pattern strings parsed afterwards with the same parser
+pub struct PatternParser {
+	macros: std::collections::HashMap<String, Vec<Row>>,
+	/// Limit for the number of rows after expanding repetitions, macros and holds, summed over
+	/// all parsed pattern strings
+	max_rows: usize,
+	num_rows: usize,
+	/// If known, lanes are checked against it
+	keymode: Option<u32>,
+	/// Whether the first problem in the input is an error, or a warning to recover from
+	strict: bool,
+	warnings: Vec<ParseWarning>,
 }
 
-fn parse_row(t: &str) -> Option<(&str, Option<Row>)> {
-	if let Some((mut t, in_brackets)) = pop_delimited(t, "[", "]") {
-		let mut move_draw_cursor_to_end_of_holds = false;
+impl PatternParser {
+	/// Each row with notes needs at least one sprite, so the sprite limit of the renderer is a
+	/// good choice for `max_rows`
+	pub fn new(max_rows: usize, keymode: Option<u32>) -> Self {
+		Self {
+			macros: Default::default(),
+			max_rows,
+			num_rows: 0,
+			keymode,
+			strict: true,
+			warnings: Vec::new(),
+		}
+	}
 
-		let mut notes = pop_until_none(in_brackets, parse_note)
-			.flatten()
-			.collect::<Vec<_>>();
+	fn warn(&mut self, warning: ParseWarning) -> Result<(), Error> {
+		if self.strict {
+			Err(warning.into_error())
+		} else {
+			self.warnings.push(warning);
+			Ok(())
+		}
+	}
+
+	fn check_row_count(&self, num_rows: usize) -> Result<(), Error> {
+		if self.num_rows.saturating_add(num_rows) > self.max_rows {
+			Err(Error::PatternTooLong {
+				limit: self.max_rows,
+			})
+		} else {
+			Ok(())
+		}
+	}
 
-		// If postfixed by `x<number>`, change entire row to hold
-		if let Some((new_t, Some(length))) = pop_literal(t, "x").and_then(parse_number) {
+	/// Parses the `x<number>` postfix of holds
+	fn parse_hold_length<'a>(
+		&mut self,
+		input: &str,
+		t: &'a str,
+	) -> Result<Option<(&'a str, u32)>, Error> {
+		let number_start = match pop_literal(t, "x") {
+			Some(number_start) => number_start,
+			None => return Ok(None),
+		};
+		match parse_number(number_start) {
+			Some((rest, Some(length))) => {
+				if is_unterminated(number_start, rest, "(", ")") {
+					self.warn(ParseWarning::UnterminatedBracket {
+						bracket: '(',
+						position: position(input, number_start),
+					})?;
+				}
+				// Placing the notes extrapolates rows up to the hold end, so this must be caught
+				// before a huge hold allocates them
+				if length as usize > self.max_rows {
+					return Err(Error::PatternTooLong {
+						limit: self.max_rows,
+					});
+				}
+				Ok(Some((rest, length)))
+			}
+			_ => Ok(None),
+		}
+	}
+
+	/// `t` must not be empty. Returns None for unknown characters
+	fn parse_note<'a>(
+		&mut self,
+		input: &str,
+		t: &'a str,
+	) -> Result<(&'a str, Option<Note>), Error> {
+		let note_start = t;
+		let mut t = t;
+		let mut note_type = NoteType::Tap;
+
+		// If prefixed with 'm', change to mine
+		if let Some(new_t) = pop_literal(t, "m") {
 			t = new_t;
+			note_type = NoteType::Mine;
+		}
 
-			if notes.is_empty() {
-				// If you write `[]x10` it should have the same effect as `0x10`
-				notes.push((Lane::Empty, NoteType::Hold { length }));
-				move_draw_cursor_to_end_of_holds = true;
-			} else {
-				for note in &mut notes {
-					note.1 = NoteType::Hold { length };
+		let (mut t, lane) = match parse_tap(t) {
+			Some((rest, Some(lane))) => {
+				if is_unterminated(t, rest, "(", ")") {
+					self.warn(ParseWarning::UnterminatedBracket {
+						bracket: '(',
+						position: position(input, t),
+					})?;
+				}
+				(rest, lane)
+			}
+			// Unknown character, or an `m` at the very end
+			invalid => {
+				let rest = invalid.map_or(t, |(rest, _)| rest);
+				self.warn(ParseWarning::UnknownCharacter {
+					text: note_start[..(note_start.len() - rest.len())].to_owned(),
+					position: position(input, note_start),
+				})?;
+				return Ok((rest, None));
+			}
+		};
+
+		if let (Some(keymode), Some(lane)) = (self.keymode, to_structures_lane(lane)) {
+			let column = lane.column_number_with_keymode(keymode);
+			if column >= keymode {
+				self.warn(ParseWarning::LaneExceedsKeymode {
+					human_readable_lane: column as usize + 1,
+					keymode: keymode as usize,
+					position: position(input, note_start),
+				})?;
+			}
+		}
+
+		// If postfixed by `x<number>`, change to hold
+		if let Some((new_t, length)) = self.parse_hold_length(input, t)? {
+			t = new_t;
+			note_type = NoteType::Hold { length };
+		}
+
+		Ok((t, Some((lane, note_type))))
+	}
+
+	/// `t` must not be empty. Returns None if the row consists of only unknown characters
+	fn parse_row<'a>(&mut self, input: &str, t: &'a str) -> Result<(&'a str, Option<Row>), Error> {
+		let row_start = t;
+		if let Some((mut t, mut in_brackets)) = pop_delimited(t, "[", "]") {
+			if is_unterminated(row_start, t, "[", "]") {
+				self.warn(ParseWarning::UnterminatedBracket {
+					bracket: '[',
+					position: position(input, row_start),
+				})?;
+			}
+			let mut move_draw_cursor_to_end_of_holds = false;
+
+			let mut notes = Vec::new();
+			while !in_brackets.is_empty() {
+				let (rest, note) = self.parse_note(input, in_brackets)?;
+				in_brackets = rest;
+				notes.extend(note);
+			}
+
+			// If postfixed by `x<number>`, change entire row to hold
+			if let Some((new_t, length)) = self.parse_hold_length(input, t)? {
+				t = new_t;
+
+				if notes.is_empty() {
+					// If you write `[]x10` it should have the same effect as `0x10`
+					notes.push((Lane::Empty, NoteType::Hold { length }));
+					move_draw_cursor_to_end_of_holds = true;
+				} else {
+					for note in &mut notes {
+						note.1 = NoteType::Hold { length };
+					}
 				}
 			}
+
+			let row = Row {
+				notes,
+				move_draw_cursor_to_end_of_holds,
+			};
+			Ok((t, Some(row)))
+		} else {
+			let (t, note) = self.parse_note(input, t)?;
+			let row = note.map(|note| Row {
+				notes: vec![note],
+				move_draw_cursor_to_end_of_holds: true,
+			});
+			Ok((t, row))
+		}
+	}
+
+	/// Parses rows until the end of the input or, if `group_start` is given, until the `}`
+	/// closing that group
+	fn parse_sequence<'a>(
+		&mut self,
+		input: &str,
+		mut t: &'a str,
+		group_start: Option<&str>,
+	) -> Result<(&'a str, Vec<Row>), Error> {
+		let mut rows = Vec::new();
+		loop {
+			t = t.trim_start();
+			if t.is_empty() {
+				break;
+			}
+			if group_start.is_some() {
+				if let Some(rest) = t.strip_prefix('}') {
+					return Ok((rest, rows));
+				}
+			}
+
+			let (rest, new_rows) = if let Some(in_group) = t.strip_prefix('{') {
+				let (rest, group_rows) = self.parse_sequence(input, in_group, Some(t))?;
+				self.parse_repetition(input, rest, group_rows)?
+			} else if t.starts_with('<') {
+				self.parse_macro(input, t)?
+			} else {
+				let (rest, row) = self.parse_row(input, t)?;
+				(rest, row.into_iter().collect())
+			};
+			t = rest;
+			rows.extend(new_rows);
+			self.check_row_count(rows.len())?;
+		}
+
+		if let Some(group_start) = group_start {
+			self.warn(ParseWarning::UnterminatedBracket {
+				bracket: '{',
+				position: position(input, group_start),
+			})?;
 		}
+		Ok((t, rows))
+	}
 
-		let row = Row {
-			notes,
-			move_draw_cursor_to_end_of_holds,
+	/// Repeats the rows if `t` starts with `*<number>`
+	fn parse_repetition<'a>(
+		&self,
+		input: &str,
+		t: &'a str,
+		rows: Vec<Row>,
+	) -> Result<(&'a str, Vec<Row>), Error> {
+		let after_star = match t.strip_prefix('*') {
+			Some(after_star) => after_star,
+			None => return Ok((t, rows)),
 		};
-		Some((t, Some(row)))
-	} else {
-		let (t, note) = parse_note(t)?;
-		let row = note.map(|note| Row {
-			notes: vec![note],
-			move_draw_cursor_to_end_of_holds: true,
-		});
-		Some((t, row))
+
+		let num_digits = after_star
+			.find(|c: char| !c.is_ascii_digit())
+			.unwrap_or(after_star.len());
+		let count = after_star[..num_digits].parse::<usize>().map_err(|_| {
+			Error::InvalidRepetitionCount {
+				position: position(input, t),
+			}
+		})?;
+		// Repeating nothing any number of times is still nothing, and looping over a huge count
+		// would hang
+		if rows.is_empty() {
+			return Ok((&after_star[num_digits..], rows));
+		}
+		self.check_row_count(rows.len().saturating_mul(count))?;
+
+		let mut repeated_rows = Vec::with_capacity(rows.len() * count);
+		for _ in 0..count {
+			repeated_rows.extend(rows.iter().cloned());
+		}
+		Ok((&after_star[num_digits..], repeated_rows))
 	}
-}
 
-pub fn parse_pattern(pattern: &str) -> structures::Pattern {
-	// Parse the pattern into internal representation, then convert to more practical representation
-	let mut rows = Vec::new();
-	for row in pop_until_none(pattern, parse_row).flatten() {
-		let mut extra_empty_rows_to_insert = 0;
-		if row.move_draw_cursor_to_end_of_holds {
+	/// Parses a macro definition `<name>={...}`, which results in no rows, or a macro use `<name>`
+	fn parse_macro<'a>(&mut self, input: &str, t: &'a str) -> Result<(&'a str, Vec<Row>), Error> {
+		let (rest, name) = match t.find('>') {
+			Some(end) => (&t[(end + 1)..], t[1..end].to_lowercase()),
+			None => {
+				return Err(Error::UnclosedDelimiter {
+					delimiter: '<',
+					position: position(input, t),
+				})
+			}
+		};
+
+		if let Some(definition) = rest.strip_prefix('=') {
+			let in_group =
+				definition
+					.strip_prefix('{')
+					.ok_or_else(|| Error::InvalidMacroDefinition {
+						name: name.clone(),
+						position: position(input, t),
+					})?;
+			let (rest, group_rows) = self.parse_sequence(input, in_group, Some(definition))?;
+			let (rest, rows) = self.parse_repetition(input, rest, group_rows)?;
+			self.macros.insert(name, rows);
+			Ok((rest, Vec::new()))
+		} else {
+			let rows = match self.macros.get(&name) {
+				Some(rows) => rows.clone(),
+				None => {
+					return Err(Error::UnknownMacro {
+						name,
+						position: position(input, t),
+					})
+				}
+			};
+			self.parse_repetition(input, rest, rows)
+		}
+	}
+
+	/// Fails on the first problem in the pattern string
+	pub fn parse(&mut self, pattern: &str) -> Result<structures::Pattern, Error> {
+		self.strict = true;
+		self.parse_rows(pattern)
+	}
+
+	/// Skips unknown characters, closes unterminated brackets and lets lanes exceed the keymode,
+	/// but returns a warning for each of those problems. Problems that can't be recovered from
+	/// are still errors
+	pub fn parse_with_warnings(
+		&mut self,
+		pattern: &str,
+	) -> Result<(structures::Pattern, Vec<ParseWarning>), Error> {
+		self.strict = false;
+		let pattern = self.parse_rows(pattern);
+		let warnings = std::mem::take(&mut self.warnings);
+		Ok((pattern?, warnings))
+	}
+
+	fn parse_rows(&mut self, pattern: &str) -> Result<structures::Pattern, Error> {
+		// Parse the pattern into internal representation, then convert to more practical
+		// representation
+		let (_, internal_rows) = self.parse_sequence(pattern, pattern, None)?;
+
+		let mut rows = Vec::new();
+		// Holds of bracketed rows don't move the cursor, but still extend the pattern
+		let mut num_rows_including_holds = 0;
+		for row in internal_rows {
 			let max_hold_length = row
 				.notes
 				.iter()
 				.filter_map(|(_, note_type)| match note_type {
-					NoteType::Hold { length } => Some(length),
+					NoteType::Hold { length } => Some(*length),
 					_ => None,
 				})
-				.max();
-			if let Some(max_hold_length) = max_hold_length {
-				extra_empty_rows_to_insert = max_hold_length.saturating_sub(1);
+				.max()
+				.unwrap_or(1);
+			num_rows_including_holds =
+				num_rows_including_holds.max(rows.len() + max_hold_length as usize);
+			self.check_row_count(num_rows_including_holds)?;
+
+			let extra_empty_rows_to_insert = match row.move_draw_cursor_to_end_of_holds {
+				true => max_hold_length.saturating_sub(1),
+				false => 0,
+			};
+
+			let notes = row
+				.notes
+				.into_iter()
+				.filter_map(|(lane, note_type)| Some((to_structures_lane(lane)?, note_type)))
+				.collect::<Vec<_>>();
+			rows.push(structures::Row { notes });
+
+			for _ in 0..extra_empty_rows_to_insert {
+				rows.push(structures::Row { notes: vec![] });
 			}
 		}
+		self.num_rows += num_rows_including_holds;
 
-		let notes = row
-			.notes
-			.into_iter()
-			.filter_map(|(lane, note_type)| {
-				let lane = match lane {
-					Lane::Index(num) => structures::Lane::Index(num),
-					Lane::Left => structures::Lane::Left,
-					Lane::Down => structures::Lane::Down,
-					Lane::Up => structures::Lane::Up,
-					Lane::Right => structures::Lane::Right,
-					Lane::Empty => return None,
-				};
-				Some((lane, note_type))
-			})
-			.collect::<Vec<_>>();
-		rows.push(structures::Row { notes });
+		Ok(structures::Pattern { rows })
+	}
+}
 
-		for _ in 0..extra_empty_rows_to_insert {
-			rows.push(structures::Row { notes: vec![] });
-		}
+/// Parses a single pattern string. See [`PatternParser`] for the syntax
+pub fn parse_pattern(pattern: &str, max_rows: usize) -> Result<structures::Pattern, Error> {
+	PatternParser::new(max_rows, None).parse(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hold_longer_than_row_limit_is_rejected() {
+		assert!(matches!(
+			parse_pattern("[1]x(50000000)", 1000),
+			Err(Error::PatternTooLong { limit: 1000 })
+		));
+		assert!(matches!(
+			parse_pattern("1x(50000000)", 1000),
+			Err(Error::PatternTooLong { limit: 1000 })
+		));
+		assert!(parse_pattern("[1]x(1000)", 1000).is_ok());
+	}
+
+	#[test]
+	fn empty_repetitions_are_instant() {
+		let huge_count = "18446744073709551615";
+		let pattern = parse_pattern(&format!("{{}}*{} 1234", huge_count), 1000).unwrap();
+		assert_eq!(pattern.rows.len(), 4);
+
+		let pattern = parse_pattern(&format!("<a>={{}} <a>*{}", huge_count), 1000).unwrap();
+		assert!(pattern.rows.is_empty());
 	}
 
-	structures::Pattern { rows }
+	#[test]
+	fn row_limit_counts_holds_of_chords() {
+		assert!(matches!(
+			parse_pattern("{[12]x(999)}*3", 1000),
+			Err(Error::PatternTooLong { limit: 1000 })
+		));
+		assert!(matches!(
+			parse_pattern("{1 [12]x(999)}*2", 1000),
+			Err(Error::PatternTooLong { limit: 1000 })
+		));
+		assert!(parse_pattern("{[12]x(500)}*2", 1000).is_ok());
+
+		// Pattern strings parsed afterwards count towards the same limit
+		let mut parser = PatternParser::new(1000, None);
+		assert!(parser.parse("[12]x(600)").is_ok());
+		assert!(matches!(
+			parser.parse("[12]x(600)"),
+			Err(Error::PatternTooLong { limit: 1000 })
+		));
+	}
 }
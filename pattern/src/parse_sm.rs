@@ -0,0 +1,149 @@
+//! Parses StepMania note data (the `#NOTES` block of .sm and .ssc files)
+
+use super::*;
+
+/// Strips `//` comments and skips metadata lines like `#NOTES:` or `dance-single:`
+fn sm_lines(text: &str) -> impl Iterator<Item = &str> {
+	text.lines()
+		.map(|line| line.find("//").map_or(line, |i| &line[..i]))
+		.filter(|line| !line.contains(':') && !line.contains('#'))
+}
+
+fn is_sm_row(token: &str) -> bool {
+	!token.is_empty()
+		&& token
+			.chars()
+			.all(|c| matches!(c, '0' | '1' | '2' | '3' | '4' | 'M' | 'L' | 'F' | 'K' | 'A'))
+}
+
+/// Returns true if the text contains at least four lines which consist only of a StepMania note
+/// row, and some of them contain empty columns. Used to distinguish .sm excerpts from the
+/// `+pattern` syntax
+pub fn looks_like_sm_notes(text: &str) -> bool {
+	let row_lines = sm_lines(text)
+		.map(|line| line.trim().trim_end_matches(|c| c == ',' || c == ';'))
+		.filter(|line| is_sm_row(line))
+		.collect::<Vec<_>>();
+
+	row_lines.len() >= 4 && row_lines.iter().any(|line| line.contains('0'))
+}
+
+enum SmToken<'a> {
+	Row(&'a str),
+	EndOfMeasure,
+	EndOfNotes,
+}
+
+fn sm_tokens(text: &str) -> impl Iterator<Item = SmToken<'_>> {
+	sm_lines(text)
+		.flat_map(|line| line.split_whitespace())
+		.flat_map(|word| {
+			// measure separators may be glued to a row, like `0000,`
+			let mut pieces = Vec::new();
+			let mut rest = word;
+			while let Some(i) = rest.find(|c| c == ',' || c == ';') {
+				pieces.push(&rest[..i]);
+				pieces.push(&rest[i..(i + 1)]);
+				rest = &rest[(i + 1)..];
+			}
+			pieces.push(rest);
+			pieces
+		})
+		.filter_map(|piece| match piece {
+			"," => Some(SmToken::EndOfMeasure),
+			";" => Some(SmToken::EndOfNotes),
+			// anything else, like code block ticks or +pattern options, is ignored
+			row if is_sm_row(row) => Some(SmToken::Row(row)),
+			_ => None,
+		})
+}
+
+/// Parses the measures of StepMania note data, like `0000 1000 0100 0010 , 1001 ...`, into one
+/// pattern segment per measure. The snap of each measure is inferred from its number of rows
+pub fn parse_sm_notes(text: &str) -> Result<Vec<(Pattern, FractionalSnap)>, Error> {
+	let mut measures: Vec<Vec<&str>> = vec![Vec::new()];
+	for token in sm_tokens(text) {
+		match token {
+			SmToken::Row(row) => {
+				// UNWRAP: the list of measures is never empty
+				measures.last_mut().unwrap().push(row);
+			}
+			SmToken::EndOfMeasure => measures.push(Vec::new()),
+			SmToken::EndOfNotes => break,
+		}
+	}
+	measures.retain(|measure| !measure.is_empty());
+
+	let expected_width = measures
+		.iter()
+		.flatten()
+		.next()
+		.ok_or(Error::EmptyPattern)?
+		.len();
+
+	let mut segments = Vec::new();
+	// For each lane, the hold that is currently being held: its flattened row index and where
+	// to find it in `segments` (segment index, row index, note index)
+	let mut open_holds = vec![None; expected_width];
+	// Hold lengths are only known once we reach the tail, so they're filled in at the end
+	let mut hold_lengths = Vec::new();
+	let mut flat_row_index = 0;
+	for measure in measures {
+		let mut pattern = Pattern::default();
+		for row in measure {
+			if row.len() != expected_width {
+				return Err(Error::InconsistentRowWidth {
+					row: row.to_owned(),
+					expected: expected_width,
+				});
+			}
+
+			let mut notes = Vec::new();
+			for (lane, note_char) in row.chars().enumerate() {
+				let note_type = match note_char {
+					'1' => NoteType::Tap,
+					'M' => NoteType::Mine,
+					'L' => NoteType::Lift,
+					'F' => NoteType::Fake,
+					'2' => NoteType::Hold { length: 1 },
+					'4' => NoteType::Roll { length: 1 },
+					'3' => {
+						if let Some((start, location)) = open_holds[lane].take() {
+							hold_lengths.push((location, flat_row_index - start + 1));
+						}
+						continue;
+					}
+					_ => continue,
+				};
+
+				if let NoteType::Hold { .. } | NoteType::Roll { .. } = note_type {
+					let location = (segments.len(), pattern.rows.len(), notes.len());
+					open_holds[lane] = Some((flat_row_index, location));
+				}
+				notes.push((Lane::Index(lane as u32), note_type));
+			}
+
+			pattern.rows.push(Row { notes });
+			flat_row_index += 1;
+		}
+
+		// UNWRAP: empty measures were removed above
+		let snap = FractionalSnap::from_snap_number(pattern.rows.len() as u32).unwrap();
+		segments.push((pattern, snap));
+	}
+
+	// Holds without a tail in the pasted excerpt are drawn until the end of the excerpt
+	for (start, location) in open_holds.into_iter().flatten() {
+		hold_lengths.push((location, flat_row_index - start));
+	}
+	for ((segment_i, row_i, note_i), hold_length) in hold_lengths {
+		let (pattern, _) = &mut segments[segment_i];
+		if let NoteType::Hold { length } | NoteType::Roll { length } =
+			&mut pattern.rows[row_i].notes[note_i].1
+		{
+			*length = hold_length as u32;
+		}
+	}
+
+	Ok(segments)
+}
@@ -0,0 +1,119 @@
+//! Places the rows of pattern segments on an absolute 192nd grid
+
+use super::*;
+
+/// Assigns each row of a pattern its position in 192nd steps. Holds may extend beyond the last
+/// row of the pattern, so positions past the end are extrapolated using the last segment's snap
+struct RowPositions {
+	positions: Vec<usize>,
+	segment_starts: Vec<usize>,
+	next_position: usize,
+	last_snap_intervals: Option<Iter192ndIntervals>,
+}
+
+impl RowPositions {
+	fn new(pattern: &[(Pattern, FractionalSnap)]) -> Self {
+		let mut positions = Vec::new();
+		let mut segment_starts = Vec::new();
+		let mut next_position = 0;
+		let mut last_snap_intervals = None;
+		for (pattern, snap) in pattern {
+			segment_starts.push(next_position);
+			let snap_192nd_intervals = last_snap_intervals.insert(snap.iter_192nd_intervals());
+			for _ in &pattern.rows {
+				positions.push(next_position);
+				next_position += snap_192nd_intervals.next_interval() as usize;
+			}
+		}
+
+		Self {
+			positions,
+			segment_starts,
+			next_position,
+			last_snap_intervals,
+		}
+	}
+
+	fn get(&mut self, row_index: usize) -> usize {
+		while self.positions.len() <= row_index {
+			self.positions.push(self.next_position);
+			self.next_position += match &mut self.last_snap_intervals {
+				Some(snap_192nd_intervals) => snap_192nd_intervals.next_interval() as usize,
+				None => 0,
+			};
+		}
+		self.positions[row_index]
+	}
+
+	fn last(&self) -> Option<usize> {
+		self.positions.last().copied()
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlacedNote {
+	pub lane: Lane,
+	pub note_type: NoteType,
+	/// Position in 192nd steps
+	pub row: usize,
+	/// Position of the tail in 192nd steps, for holds and rolls
+	pub end_row: Option<usize>,
+	/// Snap of the pattern segment that the note was written in
+	pub snap: FractionalSnap,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlacedPattern {
+	pub notes: Vec<PlacedNote>,
+	/// Position of the last row in 192nd steps. May be later than the last note if the pattern
+	/// ends with empty rows
+	pub last_row: usize,
+	/// Position of the first row of each pattern segment in 192nd steps
+	pub segment_starts: Vec<usize>,
+}
+
+impl PlacedPattern {
+	/// Position of the last row or hold end, whichever comes later
+	pub fn highest_row(&self) -> usize {
+		self.notes
+			.iter()
+			.map(|note| note.end_row.unwrap_or(note.row))
+			.fold(self.last_row, usize::max)
+	}
+}
+
+/// Resolves the snaps and hold lengths of the given pattern segments into absolute positions
+pub fn place_notes(pattern: &[(Pattern, FractionalSnap)]) -> PlacedPattern {
+	let mut row_positions = RowPositions::new(pattern);
+	// before any positions past the end are extrapolated for holds
+	let last_row = row_positions.last().unwrap_or(0);
+	let mut notes = Vec::new();
+	for (row_index, (row_data, snap)) in pattern
+		.iter()
+		.flat_map(|&(ref pattern, snap)| pattern.rows.iter().map(move |row| (row, snap)))
+		.enumerate()
+	{
+		let row = row_positions.get(row_index);
+		for &(lane, note_type) in &row_data.notes {
+			let end_row = match note_type {
+				NoteType::Hold { length } | NoteType::Roll { length } => {
+					Some(row_positions.get(row_index + (length as usize).saturating_sub(1)))
+				}
+				NoteType::Tap | NoteType::Mine | NoteType::Lift | NoteType::Fake => None,
+			};
+			notes.push(PlacedNote {
+				lane,
+				note_type,
+				row,
+				end_row,
+				snap,
+			});
+		}
+	}
+
+	PlacedPattern {
+		notes,
+		last_row,
+		segment_starts: row_positions.segment_starts,
+	}
+}
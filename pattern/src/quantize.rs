@@ -0,0 +1,266 @@
+//! Reconstructs a row grid from note timestamps, for example from time-based ArrowVortex
+//! clipboard data
+
+/// Number of 192nd rows in a beat
+const ROWS_PER_BEAT: f64 = 48.0;
+
+/// Spacings of common snaps in 192nd rows, from coarse to fine: 4ths, 8ths, 12ths, 16ths, 24ths,
+/// 32nds, 48ths, 64ths
+const GRID_SPACINGS: &[f64] = &[48.0, 24.0, 16.0, 12.0, 8.0, 6.0, 4.0, 3.0];
+
+/// Timestamps further away than this (in seconds) from every common snap are rounded to the
+/// nearest 192nd instead and reported as approximated
+const TOLERANCE: f64 = 0.003;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BpmChange {
+	/// 192nd row at which the BPM changes, relative to the earliest timestamp
+	pub row: u32,
+	pub bpm: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantization {
+	/// 192nd row of each input timestamp, relative to the earliest timestamp
+	pub rows: Vec<u32>,
+	/// BPM at the earliest timestamp. Estimated from the note spacing if no BPM changes were given
+	pub bpm: f64,
+	pub bpm_was_estimated: bool,
+	/// Number of timestamps that didn't fit any common snap and were rounded to the nearest 192nd
+	pub num_approximated: usize,
+	/// Largest distance between a timestamp and its quantized position, in seconds
+	pub max_error: f64,
+}
+
+struct TempoSegment {
+	start_row: f64,
+	start_time: f64,
+	seconds_per_row: f64,
+}
+
+/// Piecewise linear mapping between time and rows
+struct TempoMap {
+	segments: Vec<TempoSegment>,
+}
+
+impl TempoMap {
+	fn constant(bpm: f64) -> Self {
+		Self::new(&[BpmChange { row: 0, bpm }])
+	}
+
+	/// `bpm_changes` must not be empty. The first BPM also applies before the first change
+	fn new(bpm_changes: &[BpmChange]) -> Self {
+		let mut bpm_changes = bpm_changes.to_vec();
+		bpm_changes.sort_by_key(|change| change.row);
+
+		let mut segments: Vec<TempoSegment> = Vec::new();
+		for change in bpm_changes {
+			let seconds_per_row = 60.0 / (change.bpm * ROWS_PER_BEAT);
+			let (start_row, start_time) = match segments.last() {
+				Some(prev) => {
+					let row = change.row as f64;
+					(
+						row,
+						prev.start_time + (row - prev.start_row) * prev.seconds_per_row,
+					)
+				}
+				None => (0.0, 0.0),
+			};
+			segments.push(TempoSegment {
+				start_row,
+				start_time,
+				seconds_per_row,
+			});
+		}
+
+		Self { segments }
+	}
+
+	fn segment_at(&self, time: f64) -> &TempoSegment {
+		self.segments
+			.iter()
+			.rev()
+			.find(|segment| segment.start_time <= time)
+			.unwrap_or(&self.segments[0])
+	}
+
+	fn row_at(&self, time: f64) -> f64 {
+		let segment = self.segment_at(time);
+		segment.start_row + (time - segment.start_time) / segment.seconds_per_row
+	}
+
+	fn seconds_per_row_at(&self, time: f64) -> f64 {
+		self.segment_at(time).seconds_per_row
+	}
+}
+
+/// Guesses the BPM by assuming that the most common distance between two notes is a 16th
+fn estimate_bpm(times: &[f64]) -> Option<f64> {
+	let mut times = times.to_vec();
+	times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	// notes in a chord don't have exactly the same timestamp
+	times.dedup_by(|b, a| *b - *a < 0.001);
+
+	let gaps = times
+		.windows(2)
+		.map(|pair| pair[1] - pair[0])
+		.collect::<Vec<_>>();
+
+	// find the gap length which has the most other gaps within 5% of it
+	let similar_gaps = |gap: f64| {
+		gaps.iter()
+			.copied()
+			.filter(move |&other| (other - gap).abs() <= gap * 0.05)
+	};
+	let most_common_gap = gaps
+		.iter()
+		.copied()
+		.max_by_key(|&gap| similar_gaps(gap).count())?;
+	let sixteenth_length =
+		similar_gaps(most_common_gap).sum::<f64>() / similar_gaps(most_common_gap).count() as f64;
+
+	let mut bpm = 60.0 / (sixteenth_length * 4.0);
+	// if the assumption is off by a factor of two, the notes just end up on a different snap
+	while bpm >= 300.0 {
+		bpm /= 2.0;
+	}
+	while bpm < 75.0 {
+		bpm *= 2.0;
+	}
+	Some(bpm)
+}
+
+fn quantize_with_tempo_map(times: &[f64], tempo_map: &TempoMap) -> Quantization {
+	// the tempo map starts at the earliest timestamp
+	let start_time = times.iter().copied().fold(f64::INFINITY, f64::min);
+
+	let mut num_approximated = 0;
+	let mut max_error: f64 = 0.0;
+	let rows = times
+		.iter()
+		.map(|&time| {
+			let exact_row = tempo_map.row_at(time - start_time);
+			let seconds_per_row = tempo_map.seconds_per_row_at(time - start_time);
+
+			let row = GRID_SPACINGS
+				.iter()
+				.map(|&spacing| (exact_row / spacing).round() * spacing)
+				.find(|&row| (row - exact_row).abs() * seconds_per_row <= TOLERANCE)
+				.unwrap_or_else(|| {
+					num_approximated += 1;
+					exact_row.round()
+				});
+
+			max_error = max_error.max((row - exact_row).abs() * seconds_per_row);
+			row.max(0.0) as u32
+		})
+		.collect();
+
+	Quantization {
+		rows,
+		bpm: 60.0 / (tempo_map.seconds_per_row_at(0.0) * ROWS_PER_BEAT),
+		bpm_was_estimated: false,
+		num_approximated,
+		max_error,
+	}
+}
+
+/// Places timestamps (in seconds) onto the 192nd row grid. If no BPM changes are given, a
+/// constant BPM is estimated from the note spacing.
+///
+/// Returns None if `times` is empty
+pub fn quantize_times(times: &[f64], bpm_changes: &[BpmChange]) -> Option<Quantization> {
+	if times.is_empty() {
+		return None;
+	}
+
+	if !bpm_changes.is_empty() {
+		return Some(quantize_with_tempo_map(times, &TempoMap::new(bpm_changes)));
+	}
+
+	// a single chord has no note spacing to estimate from, but then the BPM doesn't matter anyway
+	let estimated_bpm = estimate_bpm(times).unwrap_or(120.0);
+	let quantization = quantize_with_tempo_map(times, &TempoMap::constant(estimated_bpm));
+
+	// The estimate is based on a single gap length, so it drifts over long patterns. Fit the
+	// BPM to all quantized notes (least squares through the first note) and quantize again
+	let start_time = times.iter().copied().fold(f64::INFINITY, f64::min);
+	let (mut time_row_sum, mut row_row_sum) = (0.0, 0.0);
+	for (&time, &row) in times.iter().zip(&quantization.rows) {
+		time_row_sum += (time - start_time) * row as f64;
+		row_row_sum += row as f64 * row as f64;
+	}
+	let mut quantization = if row_row_sum > 0.0 {
+		let fitted_bpm = 60.0 / (time_row_sum / row_row_sum * ROWS_PER_BEAT);
+		quantize_with_tempo_map(times, &TempoMap::constant(fitted_bpm))
+	} else {
+		quantization
+	};
+	quantization.bpm_was_estimated = true;
+
+	Some(quantization)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const BPM_120: &[BpmChange] = &[BpmChange { row: 0, bpm: 120.0 }];
+
+	#[test]
+	fn snaps_are_detected() {
+		// At 120 BPM, a 16th is 0.125s and a 12th is 1/6s
+		let times = [1.0, 1.125, 1.25, 1.5 + 1.0 / 6.0, 1.5 + 2.0 / 6.0];
+		let quantization = quantize_times(&times, BPM_120).unwrap();
+		assert_eq!(quantization.rows, [0, 12, 24, 64, 80]);
+		assert_eq!(quantization.num_approximated, 0);
+		assert!(quantization.max_error < 1e-9);
+		assert!(!quantization.bpm_was_estimated);
+	}
+
+	#[test]
+	fn slightly_off_timestamps_snap_onto_the_grid() {
+		let quantization = quantize_times(&[0.0, 0.1265, 0.249], BPM_120).unwrap();
+		assert_eq!(quantization.rows, [0, 12, 24]);
+		assert_eq!(quantization.num_approximated, 0);
+		assert!((quantization.max_error - 0.0015).abs() < 1e-9);
+	}
+
+	#[test]
+	fn timestamps_off_every_snap_are_rounded_to_192nds() {
+		// 28.8 rows, which is 12.5ms from the nearest 64th but 2ms from the nearest 192nd
+		let quantization = quantize_times(&[0.0, 0.3], BPM_120).unwrap();
+		assert_eq!(quantization.rows, [0, 29]);
+		assert_eq!(quantization.num_approximated, 1);
+	}
+
+	#[test]
+	fn bpm_changes_are_applied() {
+		let bpm_changes = [
+			BpmChange {
+				row: 48,
+				bpm: 240.0,
+			},
+			BpmChange { row: 0, bpm: 120.0 },
+		];
+		let quantization = quantize_times(&[0.0, 0.5, 0.75, 0.8125], &bpm_changes).unwrap();
+		assert_eq!(quantization.rows, [0, 48, 96, 108]);
+		assert_eq!(quantization.bpm, 120.0);
+	}
+
+	#[test]
+	fn bpm_is_estimated_from_the_most_common_gap() {
+		// 16ths at 150 BPM with a jump and some jitter
+		let times = [5.0, 5.0005, 5.1, 5.2, 5.301, 5.4, 5.5, 5.6, 5.7];
+		let quantization = quantize_times(&times, &[]).unwrap();
+		assert!(quantization.bpm_was_estimated);
+		assert!((quantization.bpm - 150.0).abs() < 1.0);
+		assert_eq!(quantization.rows, [0, 0, 12, 24, 36, 48, 60, 72, 84]);
+		assert_eq!(quantization.num_approximated, 0);
+	}
+
+	#[test]
+	fn no_timestamps() {
+		assert_eq!(quantize_times(&[], &[]), None);
+	}
+}
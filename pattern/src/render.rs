@@ -2,16 +2,95 @@ use image::Pixel;
 
 use super::*;
 
-struct Sprite<'a> {
-	lane: usize,
-	y_pos: usize,
-	image: &'a image::RgbaImage,
+pub(crate) struct Sprite<'a> {
+	pub lane: usize,
+	pub y_pos: usize,
+	pub image: &'a image::RgbaImage,
+	/// If set, the image is stretched vertically from the center of `y_pos` until it touches the
+	/// sprite at this position. Used for hold bodies
+	pub stretch_to_y_pos: Option<usize>,
+	pub flip_vertically: bool,
+	/// Multiplied onto the alpha channel of the image
+	pub opacity: f32,
+	/// Multiplied onto the color channels of the image. Used with grayscale textures
+	pub tint: Option<image::Rgba<u8>>,
 }
 
-struct SpriteMap<'a> {
-	sprites: Vec<Sprite<'a>>,
-	sprite_resolution: usize,
-	vertical_spacing_multiplier: f32,
+impl<'a> Sprite<'a> {
+	fn new(lane: usize, y_pos: usize, image: &'a image::RgbaImage) -> Self {
+		Self {
+			lane,
+			y_pos,
+			image,
+			stretch_to_y_pos: None,
+			flip_vertically: false,
+			opacity: 1.0,
+			tint: None,
+		}
+	}
+}
+
+pub(crate) struct SpriteMap<'a> {
+	pub sprites: Vec<Sprite<'a>>,
+	pub sprite_resolution: usize,
+	pub vertical_spacing_multiplier: f32,
+}
+
+impl SpriteMap<'_> {
+	/// Width and height in pixels, big enough to fit all the lanes and arrows
+	pub(crate) fn dimensions(&self) -> Result<(usize, usize), Error> {
+		let max_lane = self
+			.sprites
+			.iter()
+			.map(|s| s.lane)
+			.max()
+			.ok_or(Error::EmptyPattern)?;
+		let max_y_pos = self
+			.sprites
+			.iter()
+			.map(|s| s.y_pos)
+			.max()
+			.ok_or(Error::EmptyPattern)?;
+
+		let width = self.sprite_resolution * (max_lane + 1);
+		let height = self.y_pos_to_pixels(max_y_pos) + self.sprite_resolution;
+		Ok((width, height))
+	}
+
+	/// Top edge of a sprite at the given position
+	pub(crate) fn y_pos_to_pixels(&self, y_pos: usize) -> usize {
+		((y_pos * self.sprite_resolution) as f32 * self.vertical_spacing_multiplier) as usize
+	}
+
+	/// Top and bottom edge of a sprite which is stretched from the center of its position until
+	/// it touches the sprite at `stretch_to_y_pos`. None if there's no space in between
+	pub(crate) fn stretched_sprite_span(
+		&self,
+		y_pos: usize,
+		stretch_to_y_pos: usize,
+	) -> Option<(usize, usize)> {
+		let center = self.y_pos_to_pixels(y_pos) + self.sprite_resolution / 2;
+		let (top, bottom) = if stretch_to_y_pos >= y_pos {
+			(center, self.y_pos_to_pixels(stretch_to_y_pos))
+		} else {
+			(
+				self.y_pos_to_pixels(stretch_to_y_pos) + self.sprite_resolution,
+				center,
+			)
+		};
+		if bottom > top {
+			Some((top, bottom))
+		} else {
+			None
+		}
+	}
+}
+
+pub(crate) fn tint_pixel(mut pixel: image::Rgba<u8>, tint: image::Rgba<u8>) -> image::Rgba<u8> {
+	for i in 0..3 {
+		pixel[i] = (pixel[i] as u16 * tint[i] as u16 / 255) as u8;
+	}
+	pixel
 }
 
 fn copy_from(
@@ -19,6 +98,8 @@ fn copy_from(
 	other: &image::RgbaImage,
 	x: u32,
 	y: u32,
+	opacity: f32,
+	tint: Option<image::Rgba<u8>>,
 ) -> image::ImageResult<()> {
 	// Do bounds checking here so we can use the non-bounds-checking
 	// functions to copy pixels.
@@ -32,36 +113,25 @@ fn copy_from(
 
 	for i in 0..other.width() {
 		for k in 0..other.height() {
-			let p = other.get_pixel(i, k);
-			this.get_pixel_mut(i + x, k + y).blend(p);
+			let mut p = *other.get_pixel(i, k);
+			if let Some(tint) = tint {
+				p = tint_pixel(p, tint);
+			}
+			p[3] = (p[3] as f32 * opacity) as u8;
+			this.get_pixel_mut(i + x, k + y).blend(&p);
 		}
 	}
 	Ok(())
 }
 
-fn render_sprite_map(
+pub(crate) fn render_sprite_map(
 	sprite_map: SpriteMap<'_>,
 	(max_width, max_height): (usize, usize),
 ) -> Result<image::RgbaImage, Error> {
 	let sprite_res = sprite_map.sprite_resolution;
 
-	let max_lane = sprite_map
-		.sprites
-		.iter()
-		.map(|s| s.lane)
-		.max()
-		.ok_or(Error::EmptyPattern)?;
-	let max_y_pos = sprite_map
-		.sprites
-		.iter()
-		.map(|s| s.y_pos)
-		.max()
-		.ok_or(Error::EmptyPattern)?;
-
 	// Create an empty image buffer, big enough to fit all the lanes and arrows
-	let width = sprite_res * (max_lane + 1);
-	let height = ((sprite_res * max_y_pos) as f32 * sprite_map.vertical_spacing_multiplier)
-		as usize + sprite_res;
+	let (width, height) = sprite_map.dimensions()?;
 	if width > max_width || height > max_height {
 		return Err(Error::ImageTooLarge {
 			width,
@@ -72,19 +142,155 @@ fn render_sprite_map(
 	}
 	let mut buffer = image::ImageBuffer::new(width as u32, height as u32);
 
-	for sprite in sprite_map.sprites {
+	for sprite in &sprite_map.sprites {
 		let x = sprite.lane * sprite_res;
-		let y =
-			((sprite.y_pos * sprite_res) as f32 * sprite_map.vertical_spacing_multiplier) as usize;
-		// buffer.copy_from(sprite.image, x as u32, y as u32)
-		// 	.expect("Note image is too large (shouldn't happen)");
-		copy_from(&mut buffer, sprite.image, x as u32, y as u32)
+
+		let flipped_image;
+		let image = if sprite.flip_vertically {
+			flipped_image = image::imageops::flip_vertical(sprite.image);
+			&flipped_image
+		} else {
+			sprite.image
+		};
+
+		if let Some(stretch_to_y_pos) = sprite.stretch_to_y_pos {
+			if let Some((top, bottom)) =
+				sprite_map.stretched_sprite_span(sprite.y_pos, stretch_to_y_pos)
+			{
+				let stretched_image = image::imageops::resize(
+					image,
+					sprite_res as u32,
+					(bottom - top) as u32,
+					image::imageops::FilterType::Triangle,
+				);
+				copy_from(
+					&mut buffer,
+					&stretched_image,
+					x as u32,
+					top as u32,
+					sprite.opacity,
+					sprite.tint,
+				)
+				.expect("Hold body image is too large (shouldn't happen)");
+			}
+		} else {
+			// buffer.copy_from(sprite.image, x as u32, y as u32)
+			// 	.expect("Note image is too large (shouldn't happen)");
+			copy_from(
+				&mut buffer,
+				image,
+				x as u32,
+				sprite_map.y_pos_to_pixels(sprite.y_pos) as u32,
+				sprite.opacity,
+				sprite.tint,
+			)
 			.expect("Note image is too large (shouldn't happen)");
+		}
 	}
 
 	Ok(buffer)
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoteColoring {
+	/// Color notes by the snap of the pattern segment they were written in, instead of by their
+	/// position in the beat
+	pub rhythm: bool,
+	/// Instead of the colored note textures, draw a grayscale note tinted with [`snap_color`].
+	/// Works with every noteskin and gives every snap its own color, even 20ths
+	pub tint: bool,
+}
+
+/// Lays out the sprites of all notes, hold bodies and tails of a pattern, with positions in 192nd
+/// rows. Receptors are not included
+pub(crate) fn note_sprites<'a>(
+	noteskin: &'a Noteskin,
+	scroll_direction: etterna::ScrollDirection,
+	keymode: usize,
+	placed_pattern: &PlacedPattern,
+	note_coloring: NoteColoring,
+) -> Result<Vec<Sprite<'a>>, Error> {
+	let notes = placed_pattern
+		.notes
+		.iter()
+		.map(|note| (note.lane, note.note_type, note.row, note.end_row, note.snap))
+		.collect::<Vec<_>>();
+	let highest_row = placed_pattern.highest_row();
+	let row_to_y_pos = |row_number: usize| match scroll_direction {
+		etterna::ScrollDirection::Upscroll => row_number,
+		etterna::ScrollDirection::Downscroll => highest_row - row_number,
+	};
+
+	let mut sprites = Vec::new();
+
+	// hold bodies and tails first, so that they're drawn below the notes
+	for &(note_lane, note_type, row_number, end_row_number, _) in &notes {
+		let end_row_number = match end_row_number {
+			Some(x) => x,
+			None => continue,
+		};
+		let note_lane = note_lane.column_number_with_keymode(keymode as u32) as usize;
+
+		let (body, tail) = match note_type {
+			NoteType::Roll { .. } => (noteskin.roll_body(), noteskin.roll_tail()),
+			_ => (noteskin.hold_body(), noteskin.hold_tail()),
+		};
+		sprites.push(Sprite {
+			stretch_to_y_pos: Some(row_to_y_pos(end_row_number)),
+			..Sprite::new(note_lane, row_to_y_pos(row_number), body)
+		});
+		sprites.push(Sprite {
+			flip_vertically: scroll_direction == etterna::ScrollDirection::Downscroll,
+			..Sprite::new(note_lane, row_to_y_pos(end_row_number), tail)
+		});
+	}
+
+	for &(note_lane, note_type, row_number, _, segment_snap) in &notes {
+		let note_lane = note_lane.column_number_with_keymode(keymode as u32) as usize;
+
+		let y_pos = row_to_y_pos(row_number);
+		let snap = if note_coloring.rhythm {
+			segment_snap
+		} else {
+			FractionalSnap::from_row(row_number)
+		};
+		// Mines don't need a note texture, which the noteskin may not have for this snap
+		let note = || -> Result<Sprite<'a>, Error> {
+			Ok(if note_coloring.tint {
+				Sprite {
+					tint: Some(snap_color(snap)),
+					..Sprite::new(
+						note_lane,
+						y_pos,
+						noteskin.tintable_note(note_lane, keymode)?,
+					)
+				}
+			} else {
+				Sprite::new(note_lane, y_pos, noteskin.note(note_lane, keymode, snap)?)
+			})
+		};
+		match note_type {
+			NoteType::Tap | NoteType::Hold { .. } | NoteType::Roll { .. } => {
+				sprites.push(note()?);
+			}
+			NoteType::Mine => sprites.push(Sprite::new(note_lane, y_pos, noteskin.mine()?)),
+			NoteType::Lift => {
+				sprites.push(note()?);
+				sprites.push(Sprite {
+					flip_vertically: scroll_direction == etterna::ScrollDirection::Downscroll,
+					..Sprite::new(note_lane, y_pos, noteskin.lift_marker())
+				});
+			}
+			NoteType::Fake => sprites.push(Sprite {
+				opacity: 0.4,
+				..note()?
+			}),
+		}
+	}
+
+	Ok(sprites)
+}
+
 pub struct PatternRecipe<'a> {
 	pub noteskin: &'a Noteskin,
 	pub scroll_direction: etterna::ScrollDirection,
@@ -94,73 +300,46 @@ pub struct PatternRecipe<'a> {
 	pub pattern: &'a [(Pattern, FractionalSnap)],
 	pub max_image_dimensions: (usize, usize),
 	pub max_sprites: usize,
+	pub overlays: Overlays,
+	pub note_coloring: NoteColoring,
 }
 
-/// pattern: List of simple patterns and their snap represented as the number of 192nd-steps
-pub fn draw_pattern(recipe: PatternRecipe<'_>) -> Result<image::RgbaImage, Error> {
-	let PatternRecipe {
-		noteskin,
-		scroll_direction,
-		keymode,
-		vertical_spacing_multiplier,
-		pattern,
-		max_image_dimensions,
-		max_sprites,
-	} = recipe;
-
-	let mut rows = Vec::new();
-	let mut row_number = 0;
-	for (pattern, snap) in pattern {
-		let mut snap_192nd_intervals = snap.iter_192nd_intervals();
-
-		for row_data in &pattern.rows {
-			rows.push((row_data, row_number));
-			row_number += snap_192nd_intervals.next_interval() as usize;
-		}
-	}
-	let highest_row = rows
-		.iter()
-		.map(|&(_, row_number)| row_number)
-		.max()
-		.unwrap_or(0);
+/// Lays out the receptors and notes of a pattern, spaced so that the rows of the highest snap are
+/// `vertical_spacing_multiplier` sprites apart
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn pattern_sprite_map<'a>(
+	noteskin: &'a Noteskin,
+	scroll_direction: etterna::ScrollDirection,
+	keymode: usize,
+	vertical_spacing_multiplier: f32,
+	pattern: &[(Pattern, FractionalSnap)],
+	placed_pattern: &PlacedPattern,
+	max_sprites: usize,
+	note_coloring: NoteColoring,
+) -> Result<SpriteMap<'a>, Error> {
+	let receptor_y_pos = match scroll_direction {
+		etterna::ScrollDirection::Upscroll => 0,
+		etterna::ScrollDirection::Downscroll => placed_pattern.highest_row(),
+	};
 
 	let mut sprites = Vec::new();
 
 	// place receptors first, to not overshadow any notes
-	let receptor_y_pos = match scroll_direction {
-		etterna::ScrollDirection::Upscroll => 0,
-		etterna::ScrollDirection::Downscroll => highest_row,
-	};
 	for lane in 0..keymode {
-		sprites.push(Sprite {
+		sprites.push(Sprite::new(
 			lane,
-			y_pos: receptor_y_pos,
-			image: noteskin.receptor(lane, keymode)?,
-		});
+			receptor_y_pos,
+			noteskin.receptor(lane, keymode)?,
+		));
 	}
 
-	for (row_data, row_number) in rows {
-		for &(note_lane, note_type) in &row_data.notes {
-			let note_lane = note_lane.column_number_with_keymode(keymode as u32);
-
-			sprites.push(Sprite {
-				lane: note_lane as usize,
-				y_pos: match scroll_direction {
-					etterna::ScrollDirection::Upscroll => row_number,
-					etterna::ScrollDirection::Downscroll => highest_row - row_number,
-				},
-				image: match note_type {
-					NoteType::Tap => noteskin.note(
-						note_lane as usize,
-						keymode,
-						etterna::Snap::from_row(row_number as _),
-					)?,
-					NoteType::Mine => noteskin.mine()?,
-					NoteType::Hold { .. } => return Err(Error::HoldsAreUnsupported),
-				},
-			});
-		}
-	}
+	sprites.extend(note_sprites(
+		noteskin,
+		scroll_direction,
+		keymode,
+		placed_pattern,
+		note_coloring,
+	)?);
 
 	if sprites.len() > max_sprites {
 		return Err(Error::TooManySprites {
@@ -176,13 +355,56 @@ pub fn draw_pattern(recipe: PatternRecipe<'_>) -> Result<image::RgbaImage, Error
 		.ok_or(Error::EmptyPattern)?;
 	let smallest_192nd_interval = 192.0 / highest_snap as f32;
 
-	render_sprite_map(
-		SpriteMap {
-			sprites,
+	Ok(SpriteMap {
+		sprites,
+		sprite_resolution: noteskin.sprite_resolution(),
+		vertical_spacing_multiplier: (1.0 / smallest_192nd_interval) * vertical_spacing_multiplier,
+	})
+}
+
+/// pattern: List of simple patterns and their snap represented as the number of 192nd-steps
+pub fn draw_pattern(recipe: PatternRecipe<'_>) -> Result<image::RgbaImage, Error> {
+	let PatternRecipe {
+		noteskin,
+		scroll_direction,
+		keymode,
+		vertical_spacing_multiplier,
+		pattern,
+		max_image_dimensions,
+		max_sprites,
+		overlays,
+		note_coloring,
+	} = recipe;
+
+	let placed_pattern = place_notes(pattern);
+	let sprite_map = pattern_sprite_map(
+		noteskin,
+		scroll_direction,
+		keymode,
+		vertical_spacing_multiplier,
+		pattern,
+		&placed_pattern,
+		max_sprites,
+		note_coloring,
+	)?;
+	let vertical_spacing_multiplier = sprite_map.vertical_spacing_multiplier;
+	let notes_image = render_sprite_map(sprite_map, max_image_dimensions)?;
+
+	if !overlays.any() {
+		return Ok(notes_image);
+	}
+	draw_overlays(
+		notes_image,
+		OverlayLayout {
 			sprite_resolution: noteskin.sprite_resolution(),
-			vertical_spacing_multiplier: (1.0 / smallest_192nd_interval)
-				* vertical_spacing_multiplier,
+			keymode,
+			vertical_spacing_multiplier,
+			scroll_direction,
+			highest_row: placed_pattern.highest_row(),
+			placed_pattern: &placed_pattern,
+			pattern,
 		},
+		overlays,
 		max_image_dimensions,
 	)
 }
@@ -0,0 +1,169 @@
+//! Reads song metadata and the charts of .sm and .ssc files
+
+pub struct SimfileChart {
+	/// For example `dance-single`
+	pub steps_type: String,
+	/// For example `Challenge`
+	pub difficulty: String,
+	pub meter: Option<u32>,
+	/// Note rows, with measures separated by `,`
+	pub notes: String,
+}
+
+impl SimfileChart {
+	/// Number of lanes, judging by the width of the first note row
+	pub fn keymode(&self) -> Option<usize> {
+		self.notes
+			.split(|c: char| c.is_whitespace() || c == ',')
+			.find(|token| !token.is_empty())
+			.map(|row| row.len())
+	}
+
+	/// Note data of the given measures, counting from 0. The range is cut off at the end of the
+	/// chart
+	pub fn measures(&self, range: std::ops::Range<usize>) -> String {
+		self.notes
+			.split(',')
+			.skip(range.start)
+			.take(range.end.saturating_sub(range.start))
+			.collect::<Vec<_>>()
+			.join(",")
+	}
+
+	pub fn num_measures(&self) -> usize {
+		self.notes.split(',').count()
+	}
+}
+
+#[derive(Default)]
+pub struct Simfile {
+	pub title: String,
+	pub artist: String,
+	pub charts: Vec<SimfileChart>,
+}
+
+/// Lists the `#NAME:VALUE;` tags of a simfile, with comments removed
+fn simfile_tags(text: &str) -> Vec<(String, String)> {
+	let text = text
+		.lines()
+		.map(|line| line.find("//").map_or(line, |i| &line[..i]))
+		.collect::<Vec<_>>()
+		.join("\n");
+
+	let mut tags = Vec::new();
+	let mut rest = text.as_str();
+	while let Some(tag_start) = rest.find('#') {
+		let tag = &rest[(tag_start + 1)..];
+		// Values may contain `#`, so a tag ends at `;`. A missing `;` happens in broken files; like
+		// StepMania, we then end the tag where a line starts with the next `#`
+		let semicolon = tag.find(';');
+		let next_tag_line = tag
+			.match_indices('\n')
+			.map(|(i, _)| i)
+			.find(|&i| tag[i..].trim_start().starts_with('#'));
+		let tag_end = match (semicolon, next_tag_line) {
+			(Some(a), Some(b)) => a.min(b),
+			(a, b) => a.or(b).unwrap_or(tag.len()),
+		};
+
+		if let Some((name, value)) = tag[..tag_end].split_once(':') {
+			tags.push((name.trim().to_ascii_uppercase(), value.trim().to_owned()));
+		}
+		rest = &tag[tag_end..];
+	}
+	tags
+}
+
+/// Parses the contents of a .sm or .ssc file. Unknown tags are ignored
+pub fn parse_simfile(text: &str) -> Simfile {
+	let mut simfile = Simfile::default();
+	// In .ssc files, each chart begins with `#NOTEDATA:;` and its properties are separate tags
+	let mut is_ssc = false;
+	for (name, value) in simfile_tags(text) {
+		let current_ssc_chart = match simfile.charts.last_mut() {
+			Some(chart) if is_ssc => Some(chart),
+			_ => None,
+		};
+		match (name.as_str(), current_ssc_chart) {
+			("TITLE", _) => simfile.title = value,
+			("ARTIST", _) => simfile.artist = value,
+			("NOTEDATA", _) => {
+				is_ssc = true;
+				simfile.charts.push(SimfileChart {
+					steps_type: String::new(),
+					difficulty: String::new(),
+					meter: None,
+					notes: String::new(),
+				});
+			}
+			("STEPSTYPE", Some(chart)) => chart.steps_type = value,
+			("DIFFICULTY", Some(chart)) => chart.difficulty = value,
+			("METER", Some(chart)) => chart.meter = value.parse().ok(),
+			("NOTES", Some(chart)) => chart.notes = value,
+			// .sm: `#NOTES:type:description:difficulty:meter:radar values:note data;`
+			("NOTES", None) => {
+				let fields = value.splitn(6, ':').map(str::trim).collect::<Vec<_>>();
+				if let [steps_type, _description, difficulty, meter, _radar_values, notes] = *fields
+				{
+					simfile.charts.push(SimfileChart {
+						steps_type: steps_type.to_owned(),
+						difficulty: difficulty.to_owned(),
+						meter: meter.parse().ok(),
+						notes: notes.to_owned(),
+					});
+				}
+			}
+			_ => {}
+		}
+	}
+
+	simfile
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sm() {
+		let simfile = parse_simfile(include_str!("../tests/fixtures/song.sm"));
+		assert_eq!(simfile.title, "We're #1");
+		assert_eq!(simfile.artist, "C# Major");
+		assert_eq!(simfile.charts.len(), 2);
+
+		let hard = &simfile.charts[0];
+		assert_eq!(hard.steps_type, "dance-single");
+		assert_eq!(hard.difficulty, "Hard");
+		assert_eq!(hard.meter, Some(9));
+		assert_eq!(hard.keymode(), Some(4));
+		assert_eq!(hard.num_measures(), 2);
+		assert_eq!(
+			hard.measures(1..5).split_whitespace().collect::<Vec<_>>(),
+			["1100", "0000", "0011", "0000"]
+		);
+
+		assert_eq!(simfile.charts[1].difficulty, "Challenge");
+		assert_eq!(simfile.charts[1].meter, Some(12));
+	}
+
+	#[test]
+	fn ssc() {
+		let simfile = parse_simfile(include_str!("../tests/fixtures/song.ssc"));
+		assert_eq!(simfile.title, "Sharp #Title");
+		assert_eq!(simfile.artist, "Someone");
+		assert_eq!(simfile.charts.len(), 2);
+
+		let challenge = &simfile.charts[0];
+		assert_eq!(challenge.steps_type, "dance-single");
+		assert_eq!(challenge.difficulty, "Challenge");
+		assert_eq!(challenge.meter, Some(14));
+		assert_eq!(challenge.keymode(), Some(4));
+
+		// `#METER:7` is missing its `;`, which must not swallow the notes
+		let edit = &simfile.charts[1];
+		assert_eq!(edit.steps_type, "dance-solo");
+		assert_eq!(edit.meter, Some(7));
+		assert_eq!(edit.keymode(), Some(6));
+		assert_eq!(edit.num_measures(), 1);
+	}
+}
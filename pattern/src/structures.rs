@@ -1,10 +1,10 @@
 /// Represents a note pattern without snap changes.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Pattern {
 	pub rows: Vec<Row>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Row {
 	pub notes: Vec<(Lane, NoteType)>,
 }
@@ -40,5 +40,15 @@ impl Lane {
 pub enum NoteType {
 	Tap,
 	Mine,
-	Hold { length: u32 },
+	/// `length` is the number of rows the hold spans, including the row of the hold head
+	Hold {
+		length: u32,
+	},
+	/// Like [`NoteType::Hold`], but drawn with roll textures
+	Roll {
+		length: u32,
+	},
+	Lift,
+	/// Drawn like a tap, but faded out
+	Fake,
 }
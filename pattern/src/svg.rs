@@ -0,0 +1,135 @@
+//! Renders patterns as SVG, which has no pixel limit and can be zoomed without getting blurry
+
+use super::*;
+
+pub struct SvgPatternRecipe<'a> {
+	pub noteskin: &'a Noteskin,
+	pub scroll_direction: etterna::ScrollDirection,
+	pub keymode: usize,
+	pub vertical_spacing_multiplier: f32,
+	// List of pattern segments and their snap
+	pub pattern: &'a [(Pattern, FractionalSnap)],
+	pub max_sprites: usize,
+	pub note_coloring: NoteColoring,
+}
+
+fn encode_png(image: &image::RgbaImage) -> Result<Vec<u8>, Error> {
+	let mut png_bytes = Vec::new();
+	let mut encoder = png::Encoder::new(&mut png_bytes, image.width(), image.height());
+	encoder.set_color(png::ColorType::Rgba);
+	encoder.set_depth(png::BitDepth::Eight);
+	let mut writer = encoder.write_header()?;
+	writer.write_image_data(image.as_raw())?;
+	writer.finish()?;
+	Ok(png_bytes)
+}
+
+fn render_sprite_map_svg(sprite_map: SpriteMap<'_>) -> Result<String, Error> {
+	use std::fmt::Write as _;
+
+	let sprite_res = sprite_map.sprite_resolution;
+	let (width, height) = sprite_map.dimensions()?;
+
+	// Each distinct texture is embedded once and referenced by every sprite that uses it
+	let mut texture_ids = std::collections::HashMap::<_, usize>::new();
+	let mut defs = String::new();
+	let mut body = String::new();
+	for sprite in &sprite_map.sprites {
+		let next_id = texture_ids.len();
+		let texture_id = *texture_ids
+			.entry((sprite.image as *const image::RgbaImage, sprite.tint))
+			.or_insert(next_id);
+		if texture_id == next_id {
+			let tinted_image;
+			let image = match sprite.tint {
+				Some(tint) => {
+					tinted_image = image::RgbaImage::from_fn(
+						sprite.image.width(),
+						sprite.image.height(),
+						|x, y| tint_pixel(*sprite.image.get_pixel(x, y), tint),
+					);
+					&tinted_image
+				}
+				None => sprite.image,
+			};
+
+			// UNWRAP: writing into a String can't fail
+			write!(
+				defs,
+				r#"<image id="t{}" width="{}" height="{}" preserveAspectRatio="none" href="data:image/png;base64,{}"/>"#,
+				texture_id,
+				image.width(),
+				image.height(),
+				base64::encode(encode_png(image)?),
+			)
+			.unwrap();
+		}
+
+		let x = sprite.lane * sprite_res;
+		let (top, sprite_height) = match sprite.stretch_to_y_pos {
+			Some(stretch_to_y_pos) => {
+				match sprite_map.stretched_sprite_span(sprite.y_pos, stretch_to_y_pos) {
+					Some((top, bottom)) => (top, bottom - top),
+					None => continue,
+				}
+			}
+			None => (sprite_map.y_pos_to_pixels(sprite.y_pos), sprite_res),
+		};
+
+		// Scale the texture to the sprite size, mirroring it around its center if needed
+		let vertical_scale = sprite_height as f32 / sprite.image.height() as f32;
+		let horizontal_scale = sprite_res as f32 / sprite.image.width() as f32;
+		let transform = if sprite.flip_vertically {
+			format!(
+				"translate({} {}) scale({} {})",
+				x,
+				top + sprite_height,
+				horizontal_scale,
+				-vertical_scale,
+			)
+		} else {
+			format!(
+				"translate({} {}) scale({} {})",
+				x, top, horizontal_scale, vertical_scale,
+			)
+		};
+		write!(
+			body,
+			r##"<use href="#t{}" transform="{}" opacity="{}"/>"##,
+			texture_id, transform, sprite.opacity,
+		)
+		.unwrap();
+	}
+
+	Ok(format!(
+		r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}"><defs>{}</defs>{}</svg>"#,
+		width, height, width, height, defs, body,
+	))
+}
+
+/// Like [`draw_pattern`], but returns an SVG document with the sprites embedded as PNGs. The
+/// image size isn't limited, only the number of sprites
+pub fn draw_pattern_svg(recipe: SvgPatternRecipe<'_>) -> Result<String, Error> {
+	let SvgPatternRecipe {
+		noteskin,
+		scroll_direction,
+		keymode,
+		vertical_spacing_multiplier,
+		pattern,
+		max_sprites,
+		note_coloring,
+	} = recipe;
+
+	let placed_pattern = place_notes(pattern);
+	let sprite_map = pattern_sprite_map(
+		noteskin,
+		scroll_direction,
+		keymode,
+		vertical_spacing_multiplier,
+		pattern,
+		&placed_pattern,
+		max_sprites,
+		note_coloring,
+	)?;
+	render_sprite_map_svg(sprite_map)
+}
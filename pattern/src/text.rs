@@ -0,0 +1,100 @@
+//! A tiny bitmap font for labels and captions, so that we don't need to ship a font file
+
+use imageproc::rect::Rect;
+
+pub(crate) const TEXT_COLOR: image::Rgba<u8> = image::Rgba([200, 200, 200, 255]);
+
+/// Size of a character including the spacing to the next one, in multiples of the scale
+pub(crate) const GLYPH_WIDTH: u32 = 4;
+pub(crate) const GLYPH_HEIGHT: u32 = 5;
+
+/// 3x5 pixel bitmap of a character, row by row from the top, most significant bit first.
+/// Letters are drawn in uppercase. Unsupported characters are drawn as `?`
+fn glyph(c: char) -> u16 {
+	match c.to_ascii_uppercase() {
+		'0' => 0b111_101_101_101_111,
+		'1' => 0b010_110_010_010_111,
+		'2' => 0b111_001_111_100_111,
+		'3' => 0b111_001_111_001_111,
+		'4' => 0b101_101_111_001_001,
+		'5' => 0b111_100_111_001_111,
+		'6' => 0b111_100_111_101_111,
+		'7' => 0b111_001_001_001_001,
+		'8' => 0b111_101_111_101_111,
+		'9' => 0b111_101_111_001_111,
+		'A' => 0b010_101_111_101_101,
+		'B' => 0b110_101_110_101_110,
+		'C' => 0b011_100_100_100_011,
+		'D' => 0b110_101_101_101_110,
+		'E' => 0b111_100_110_100_111,
+		'F' => 0b111_100_110_100_100,
+		'G' => 0b011_100_101_101_011,
+		'H' => 0b101_101_111_101_101,
+		'I' => 0b111_010_010_010_111,
+		'J' => 0b001_001_001_101_010,
+		'K' => 0b101_101_110_101_101,
+		'L' => 0b100_100_100_100_111,
+		'M' => 0b101_111_111_101_101,
+		'N' => 0b110_101_101_101_101,
+		'O' => 0b010_101_101_101_010,
+		'P' => 0b110_101_110_100_100,
+		'Q' => 0b010_101_101_110_011,
+		'R' => 0b110_101_110_101_101,
+		'S' => 0b011_100_010_001_110,
+		'T' => 0b111_010_010_010_010,
+		'U' => 0b101_101_101_101_111,
+		'V' => 0b101_101_101_101_010,
+		'W' => 0b101_101_111_111_101,
+		'X' => 0b101_101_010_101_101,
+		'Y' => 0b101_101_010_010_010,
+		'Z' => 0b111_001_010_100_111,
+		'[' => 0b110_100_100_100_110,
+		']' => 0b011_001_001_001_011,
+		'(' => 0b010_100_100_100_010,
+		')' => 0b010_001_001_001_010,
+		'{' => 0b011_010_110_010_011,
+		'}' => 0b110_010_011_010_110,
+		':' => 0b000_010_000_010_000,
+		'.' => 0b000_000_000_000_010,
+		',' => 0b000_000_000_010_100,
+		'-' => 0b000_000_111_000_000,
+		'+' => 0b000_010_111_010_000,
+		'*' => 0b000_101_010_101_000,
+		'/' => 0b001_001_010_100_100,
+		'=' => 0b000_111_000_111_000,
+		'!' => 0b010_010_010_000_010,
+		'_' => 0b000_000_000_000_111,
+		' ' => 0,
+		_ => 0b110_001_010_000_010,
+	}
+}
+
+pub(crate) fn text_width(text: &str, scale: u32) -> u32 {
+	text.chars().count() as u32 * GLYPH_WIDTH * scale
+}
+
+/// Draws a line of text with its top left corner at the given position
+pub(crate) fn draw_text(
+	canvas: &mut image::RgbaImage,
+	(x, y): (i32, i32),
+	text: &str,
+	scale: u32,
+	color: image::Rgba<u8>,
+) {
+	for (char_index, c) in text.chars().enumerate() {
+		let glyph = glyph(c);
+		let glyph_x = x + (char_index as u32 * GLYPH_WIDTH * scale) as i32;
+		for pixel in 0..15 {
+			if glyph & (1 << (14 - pixel)) == 0 {
+				continue;
+			}
+			let (column, row) = (pixel % 3, pixel / 3);
+			imageproc::drawing::draw_filled_rect_mut(
+				canvas,
+				Rect::at(glyph_x + (column * scale) as i32, y + (row * scale) as i32)
+					.of_size(scale, scale),
+				color,
+			);
+		}
+	}
+}
@@ -0,0 +1,302 @@
+//! Modifiers that move the notes of a pattern to different lanes, like Mirror or Shuffle in game
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+	/// Reverses the lane order
+	Mirror,
+	/// Swaps the lanes of the left and the right hand. The middle lane of odd keymodes stays
+	Flip,
+	/// Moves every note the given number of lanes to the right, wrapping around at the edge.
+	/// Negative numbers rotate to the left
+	Rotate(i32),
+	/// Permutes the lanes randomly. The same seed always gives the same permutation
+	Shuffle { seed: u64 },
+	/// Moves notes that would be hit with the same lane as the row before to the nearest free
+	/// lane, if there is one
+	NoJacks,
+	/// Spreads the lanes evenly across a different keymode, e.g. 4k to 6k. Notes that end up
+	/// in the same lane when converting to a lower keymode are merged
+	ConvertKeymode { keymode: usize },
+}
+
+impl Transform {
+	/// The keymode of the pattern after applying this transform
+	pub fn resulting_keymode(&self, keymode: usize) -> usize {
+		match *self {
+			Self::ConvertKeymode {
+				keymode: new_keymode,
+			} => new_keymode,
+			_ => keymode,
+		}
+	}
+}
+
+/// SplitMix64. The shuffle must stay the same across versions for a given seed, so we don't rely
+/// on an external random number generator
+fn next_random(state: &mut u64) -> u64 {
+	*state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+	let mut z = *state;
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+	z ^ (z >> 31)
+}
+
+/// For transforms that map each lane to a fixed new lane, returns the new lane for each lane
+fn lane_mapping(transform: Transform, keymode: usize) -> Option<Vec<u32>> {
+	let lanes = 0..keymode;
+	let mapping = match transform {
+		Transform::Mirror => lanes.map(|lane| keymode - 1 - lane).collect(),
+		Transform::Flip => {
+			let half = keymode / 2;
+			let other_half_start = (keymode + 1) / 2;
+			lanes
+				.map(|lane| {
+					if lane < half {
+						lane + other_half_start
+					} else if lane >= other_half_start {
+						lane - other_half_start
+					} else {
+						lane
+					}
+				})
+				.collect()
+		}
+		Transform::Rotate(offset) => lanes
+			.map(|lane| (lane as i64 + offset as i64).rem_euclid(keymode as i64) as usize)
+			.collect(),
+		Transform::Shuffle { seed } => {
+			// Fisher-Yates
+			let mut mapping = lanes.collect::<Vec<_>>();
+			let mut state = seed;
+			for i in (1..keymode).rev() {
+				let j = (next_random(&mut state) % (i as u64 + 1)) as usize;
+				mapping.swap(i, j);
+			}
+			mapping
+		}
+		Transform::ConvertKeymode {
+			keymode: new_keymode,
+		} => lanes
+			.map(|lane| {
+				let relative_position = lane as f32 / (keymode.max(2) - 1) as f32;
+				(relative_position * (new_keymode.max(1) - 1) as f32).round() as usize
+			})
+			.collect(),
+		Transform::NoJacks => return None,
+	};
+	Some(mapping.into_iter().map(|lane| lane as u32).collect())
+}
+
+/// Whether a note needs to be hit, as opposed to mines and fakes
+fn is_hittable(note_type: NoteType) -> bool {
+	match note_type {
+		NoteType::Tap | NoteType::Hold { .. } | NoteType::Roll { .. } | NoteType::Lift => true,
+		NoteType::Mine | NoteType::Fake => false,
+	}
+}
+
+fn remove_jacks(pattern: &mut [(Pattern, FractionalSnap)], keymode: usize) {
+	// Index of the last row that each lane is held down until
+	let mut hold_ends = vec![None; keymode];
+	let mut previous_lanes = vec![false; keymode];
+	for (row_index, row) in pattern
+		.iter_mut()
+		.flat_map(|(pattern, _)| &mut pattern.rows)
+		.enumerate()
+	{
+		let is_held = |lane: usize| hold_ends[lane].map_or(false, |end| end >= row_index);
+
+		let mut used_lanes = vec![false; keymode];
+		let mut jacked_notes = Vec::new();
+		for (i, &(lane, note_type)) in row.notes.iter().enumerate() {
+			let lane = lane.column_number_with_keymode(keymode as u32) as usize;
+			if is_hittable(note_type) && previous_lanes[lane] {
+				jacked_notes.push(i);
+			} else {
+				used_lanes[lane] = true;
+			}
+		}
+
+		for i in jacked_notes {
+			let lane = row.notes[i].0.column_number_with_keymode(keymode as u32) as usize;
+			let free_lane = (0..keymode)
+				.filter(|&other| !used_lanes[other] && !previous_lanes[other] && !is_held(other))
+				.min_by_key(|&other| (other as i64 - lane as i64).abs());
+			let new_lane = free_lane.unwrap_or(lane);
+			row.notes[i].0 = Lane::Index(new_lane as u32);
+			used_lanes[new_lane] = true;
+		}
+
+		let mut current_lanes = vec![false; keymode];
+		for &(lane, note_type) in &row.notes {
+			let lane = lane.column_number_with_keymode(keymode as u32) as usize;
+			if let NoteType::Hold { length } | NoteType::Roll { length } = note_type {
+				hold_ends[lane] = Some(row_index + (length as usize).saturating_sub(1));
+			}
+			if is_hittable(note_type) {
+				current_lanes[lane] = true;
+			}
+		}
+		// Empty rows don't break up jacks
+		if current_lanes.contains(&true) {
+			previous_lanes = current_lanes;
+		}
+	}
+}
+
+/// Applies a transform to the pattern in place. Afterwards, all lanes are [`Lane::Index`] lanes
+/// in the keymode returned by [`Transform::resulting_keymode`]
+pub fn transform_pattern(
+	pattern: &mut [(Pattern, FractionalSnap)],
+	transform: Transform,
+	keymode: usize,
+) -> Result<(), Error> {
+	// Resolve Left/Down/Up/Right first, their meaning depends on the keymode
+	for (lane, _) in pattern
+		.iter_mut()
+		.flat_map(|(pattern, _)| &mut pattern.rows)
+		.flat_map(|row| &mut row.notes)
+	{
+		let column = lane.column_number_with_keymode(keymode as u32);
+		if column as usize >= keymode {
+			return Err(Error::InvalidLaneForKeymode {
+				human_readable_lane: column as usize + 1,
+				keymode,
+			});
+		}
+		*lane = Lane::Index(column);
+	}
+
+	let mapping = match lane_mapping(transform, keymode) {
+		Some(mapping) => mapping,
+		None => {
+			remove_jacks(pattern, keymode);
+			return Ok(());
+		}
+	};
+	for row in pattern
+		.iter_mut()
+		.flat_map(|(pattern, _)| &mut pattern.rows)
+	{
+		for (lane, _) in &mut row.notes {
+			*lane = Lane::Index(mapping[lane.column_number_with_keymode(keymode as u32) as usize]);
+		}
+		// Lanes may have been merged when converting to a lower keymode
+		let mut seen_lanes = Vec::new();
+		row.notes.retain(|&(lane, _)| {
+			if seen_lanes.contains(&lane) {
+				false
+			} else {
+				seen_lanes.push(lane);
+				true
+			}
+		});
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Lanes of the notes in each row after applying the transform, sorted within each row
+	fn transformed_lanes(pattern: &str, transform: Transform, keymode: usize) -> Vec<Vec<u32>> {
+		let pattern = parse_pattern(pattern, 1000).unwrap();
+		let snap = FractionalSnap::from_snap_number(16).unwrap();
+		let mut segments = [(pattern, snap)];
+		transform_pattern(&mut segments, transform, keymode).unwrap();
+
+		let [(pattern, _)] = segments;
+		pattern
+			.rows
+			.iter()
+			.map(|row| {
+				let mut lanes = row
+					.notes
+					.iter()
+					.map(|&(lane, _)| lane.column_number_with_keymode(keymode as u32))
+					.collect::<Vec<_>>();
+				lanes.sort_unstable();
+				lanes
+			})
+			.collect()
+	}
+
+	#[test]
+	fn shuffle_depends_only_on_seed() {
+		let shuffle = |seed| lane_mapping(Transform::Shuffle { seed }, 7).unwrap();
+		assert_eq!(shuffle(42), shuffle(42));
+
+		let mut sorted_mapping = shuffle(42);
+		sorted_mapping.sort_unstable();
+		assert_eq!(sorted_mapping, (0..7).collect::<Vec<_>>());
+
+		let mut mappings = (0..10).map(shuffle).collect::<Vec<_>>();
+		mappings.dedup();
+		assert!(mappings.len() > 1);
+	}
+
+	#[test]
+	fn flip() {
+		assert_eq!(lane_mapping(Transform::Flip, 4).unwrap(), [2, 3, 0, 1]);
+		// The middle lane stays
+		assert_eq!(lane_mapping(Transform::Flip, 5).unwrap(), [3, 4, 2, 0, 1]);
+		assert_eq!(
+			transformed_lanes("1[23]4", Transform::Flip, 4),
+			[vec![2], vec![0, 3], vec![1]]
+		);
+	}
+
+	#[test]
+	fn rotate() {
+		assert_eq!(lane_mapping(Transform::Rotate(1), 4).unwrap(), [1, 2, 3, 0]);
+		assert_eq!(
+			lane_mapping(Transform::Rotate(-1), 5).unwrap(),
+			[4, 0, 1, 2, 3]
+		);
+		assert_eq!(
+			lane_mapping(Transform::Rotate(7), 5).unwrap(),
+			lane_mapping(Transform::Rotate(2), 5).unwrap()
+		);
+	}
+
+	#[test]
+	fn nojacks_leaves_no_jacks() {
+		let rows = transformed_lanes("1111 2222 [12][12][13]33[34]4", Transform::NoJacks, 4);
+		let rows = rows
+			.into_iter()
+			.filter(|lanes| !lanes.is_empty())
+			.collect::<Vec<_>>();
+		for pair in rows.windows(2) {
+			assert!(
+				pair[1].iter().all(|lane| !pair[0].contains(lane)),
+				"{:?} is a jack",
+				pair
+			);
+		}
+	}
+
+	#[test]
+	fn resulting_keymode_matches_conversion() {
+		for &(keymode, new_keymode) in &[(4, 6), (6, 4), (4, 7), (7, 4)] {
+			let transform = Transform::ConvertKeymode {
+				keymode: new_keymode,
+			};
+			assert_eq!(transform.resulting_keymode(keymode), new_keymode);
+
+			let pattern = (1..=keymode)
+				.map(|lane| lane.to_string())
+				.collect::<String>();
+			let rows = transformed_lanes(&pattern, transform, keymode);
+			let max_lane = rows.iter().flatten().max().copied().unwrap();
+			// The outer lanes stay the outer lanes
+			assert_eq!(max_lane as usize, new_keymode - 1);
+			assert_eq!(rows[0], [0]);
+		}
+
+		assert_eq!(Transform::Mirror.resulting_keymode(5), 5);
+	}
+}
@@ -0,0 +1,150 @@
+//! Wraps patterns that are too tall for a single image into several columns, like a chart preview
+//! sheet
+
+use super::*;
+
+const ROWS_PER_MEASURE: usize = 192;
+
+struct WrappedColumn {
+	pattern: Vec<(Pattern, FractionalSnap)>,
+	/// Zero-based index of the first measure in this column
+	first_measure: usize,
+	/// Zero-based index of the measure of the last row in this column
+	last_measure: usize,
+}
+
+/// Splits the pattern at every `measures_per_column`-th measure. Segments that cross a column
+/// boundary are split in two, each keeping the snap of the segment
+fn split_into_columns(
+	pattern: &[(Pattern, FractionalSnap)],
+	measures_per_column: usize,
+) -> Vec<WrappedColumn> {
+	let rows_per_column = measures_per_column * ROWS_PER_MEASURE;
+
+	let mut columns = vec![WrappedColumn {
+		pattern: Vec::new(),
+		first_measure: 0,
+		last_measure: 0,
+	}];
+	let mut position = 0;
+	for &(ref segment, snap) in pattern {
+		let mut snap_192nd_intervals = snap.iter_192nd_intervals();
+		let mut piece = Pattern::default();
+		for row in &segment.rows {
+			if position >= columns.len() * rows_per_column {
+				if !piece.rows.is_empty() {
+					// UNWRAP: there's always at least one column
+					let column = columns.last_mut().unwrap();
+					column.pattern.push((std::mem::take(&mut piece), snap));
+				}
+				columns.push(WrappedColumn {
+					pattern: Vec::new(),
+					first_measure: columns.len() * measures_per_column,
+					last_measure: 0,
+				});
+			}
+
+			// UNWRAP: there's always at least one column
+			columns.last_mut().unwrap().last_measure = position / ROWS_PER_MEASURE;
+			piece.rows.push(row.clone());
+			position += snap_192nd_intervals.next_interval() as usize;
+		}
+		if !piece.rows.is_empty() {
+			// UNWRAP: there's always at least one column
+			columns.last_mut().unwrap().pattern.push((piece, snap));
+		}
+	}
+
+	columns
+}
+
+/// Like [`draw_pattern`], but if the pattern is too tall for `max_image_dimensions`, it's split at
+/// measure boundaries into columns which are placed left to right, each with its own receptors and
+/// captioned with its measure numbers
+pub fn draw_pattern_wrapped(recipe: PatternRecipe<'_>) -> Result<image::RgbaImage, Error> {
+	let PatternRecipe {
+		noteskin,
+		scroll_direction,
+		keymode,
+		vertical_spacing_multiplier,
+		pattern,
+		max_image_dimensions,
+		max_sprites,
+		overlays,
+		note_coloring,
+	} = recipe;
+	let max_height = max_image_dimensions.1;
+
+	let too_tall_error = match draw_pattern(PatternRecipe {
+		noteskin,
+		scroll_direction,
+		keymode,
+		vertical_spacing_multiplier,
+		pattern,
+		max_image_dimensions,
+		max_sprites,
+		overlays,
+		note_coloring,
+	}) {
+		Err(e) if matches!(e, Error::ImageTooLarge { height, .. } if height > max_height) => e,
+		other => return other,
+	};
+
+	// draw_pattern spaces the rows of the lowest snap one sprite apart. Every column must use the
+	// spacing of the whole pattern, or measures would have different heights in each column
+	let lowest_snap = |pattern: &[(Pattern, FractionalSnap)]| {
+		pattern
+			.iter()
+			.map(|&(_, snap)| snap.snap_number())
+			.min()
+			.unwrap_or(1)
+	};
+	let overall_lowest_snap = lowest_snap(pattern);
+
+	let sprite_res = noteskin.sprite_resolution();
+	let measure_height =
+		(sprite_res as u32 * overall_lowest_snap) as f32 * vertical_spacing_multiplier;
+	let available_height = max_height.saturating_sub(caption_height(noteskin) + sprite_res);
+	let measures_per_column = (available_height as f32 / measure_height) as usize;
+	if measures_per_column == 0 {
+		return Err(too_tall_error);
+	}
+
+	let columns = split_into_columns(pattern, measures_per_column);
+	let captions = columns
+		.iter()
+		.map(|column| {
+			if column.first_measure == column.last_measure {
+				format!("measure {}", column.first_measure + 1)
+			} else {
+				format!(
+					"measures {}-{}",
+					column.first_measure + 1,
+					column.last_measure + 1
+				)
+			}
+		})
+		.collect::<Vec<_>>();
+	let compared_patterns = columns
+		.iter()
+		.zip(&captions)
+		.map(|(column, caption)| ComparedPattern {
+			keymode,
+			vertical_spacing_multiplier: vertical_spacing_multiplier * overall_lowest_snap as f32
+				/ lowest_snap(&column.pattern) as f32,
+			pattern: &column.pattern,
+			caption,
+		})
+		.collect::<Vec<_>>();
+
+	draw_pattern_comparison(ComparisonRecipe {
+		noteskin,
+		scroll_direction,
+		patterns: &compared_patterns,
+		gap: sprite_res,
+		max_image_dimensions,
+		max_sprites,
+		overlays,
+		note_coloring,
+	})
+}
@@ -0,0 +1,153 @@
+//! Chart command, which renders sections of charts from the local chart library
+
+use crate::{Context, Error};
+use ::pattern as pattern_draw;
+use poise::serenity_prelude as serenity;
+
+/// Limit for the number of sprites in a chart image
+const MAX_SPRITES: usize = 1000;
+/// Measures shown if the user doesn't request any
+const DEFAULT_MEASURES: std::ops::Range<usize> = 0..8;
+
+/// Parses a trailing `measures 12-20` or `measure 12` into a zero-based range of measures
+fn extract_measure_range(args: &str) -> Result<(&str, Option<std::ops::Range<usize>>), Error> {
+	let mut words = args.rsplitn(3, char::is_whitespace);
+	let (range, keyword, query) = match (words.next(), words.next(), words.next()) {
+		(Some(range), Some(keyword), query)
+			if keyword.eq_ignore_ascii_case("measures")
+				|| keyword.eq_ignore_ascii_case("measure") =>
+		{
+			(range, keyword, query.unwrap_or(""))
+		}
+		_ => return Ok((args, None)),
+	};
+
+	let parse_measure = |measure: &str| match measure.parse::<usize>() {
+		Ok(measure) if measure > 0 => Ok(measure),
+		_ => Err(anyhow::anyhow!(
+			"\"{}\" is not a valid measure number",
+			measure
+		)),
+	};
+	let (first, last) = match range.split_once('-') {
+		Some((first, last)) => (parse_measure(first)?, parse_measure(last)?),
+		None => (parse_measure(range)?, parse_measure(range)?),
+	};
+	if first > last {
+		return Err(anyhow::anyhow!(
+			"Use `{} {}-{}` instead",
+			keyword,
+			last,
+			first
+		));
+	}
+
+	Ok((query.trim(), Some((first - 1)..last)))
+}
+
+/// Render a section of a chart from the bot's chart library
+///
+/// Call this command with a chartkey or song name, and optionally the measures to show:
+/// `+chart Xabc123... measures 12-20` or `+chart Song Name measure 5`. By default, the first
+/// eight measures of the hardest chart of the song are shown. Chartkeys only work for charts
+/// listed in the library's chartkeys.json
+#[poise::command(prefix_command, track_edits, slash_command)]
+pub async fn chart(
+	ctx: Context<'_>,
+	#[rest]
+	#[description = "Chartkey or song name, optionally followed by e.g. `measures 12-20`"]
+	query: String,
+) -> Result<(), Error> {
+	let (query, measures) = extract_measure_range(&query)?;
+	if query.is_empty() {
+		return Err(anyhow::anyhow!("Please specify a chartkey or song name"));
+	}
+	// Reading the simfile blocks, so keep it off the async workers
+	let chart_library = std::sync::Arc::clone(&ctx.data().chart_library);
+	let query_owned = query.to_owned();
+	let library_chart =
+		tokio::task::spawn_blocking(move || chart_library.find(&query_owned)).await??;
+	let chart = &library_chart.chart;
+
+	let num_measures = chart.num_measures();
+	let measures = measures.unwrap_or(DEFAULT_MEASURES);
+	if measures.start >= num_measures {
+		return Err(anyhow::anyhow!(
+			"{} only has {} measures",
+			library_chart.title,
+			num_measures
+		));
+	}
+	let measures = measures.start..measures.end.min(num_measures);
+	let pattern = pattern_draw::parse_sm_notes(&chart.measures(measures.clone()))?;
+
+	let keymode = chart.keymode().ok_or(pattern_draw::Error::EmptyPattern)?;
+	let preferences = ctx.data().lock_data().pattern_preferences(ctx.author().id);
+	let scroll_direction = ctx
+		.data()
+		.lock_data()
+		.scroll(ctx.author().id)
+		.unwrap_or(etterna::ScrollDirection::Upscroll);
+	let preferred_noteskin = preferences
+		.noteskin(keymode as u32)
+		.and_then(|name| ctx.data().noteskin_provider.get(name));
+	let noteskin = match preferred_noteskin {
+		Some(noteskin) => noteskin,
+		None => ctx
+			.data()
+			.noteskin_provider
+			.default_for_keymode(keymode as u32)
+			.ok_or_else(|| anyhow::anyhow!("No noteskins are installed"))?,
+	};
+
+	let image = pattern_draw::draw_pattern_wrapped(pattern_draw::PatternRecipe {
+		noteskin,
+		scroll_direction,
+		keymode,
+		vertical_spacing_multiplier: preferences.zoom.unwrap_or(1.0)
+			* super::pattern::sm_notes_spacing_multiplier(&pattern),
+		pattern: &pattern,
+		max_image_dimensions: (5000, 10000),
+		max_sprites: MAX_SPRITES,
+		overlays: pattern_draw::Overlays::default(),
+		note_coloring: pattern_draw::NoteColoring::default(),
+	})?;
+
+	let mut img_bytes = Vec::with_capacity(1_000_000); // preallocate 1 MB for the img
+	image::DynamicImage::ImageRgba8(image)
+		.write_to(
+			&mut std::io::Cursor::new(&mut img_bytes),
+			image::ImageOutputFormat::Png,
+		)
+		.map_err(pattern_draw::Error::ImageError)?;
+
+	let measures_text = if measures.len() == 1 {
+		format!("measure {}", measures.start + 1)
+	} else {
+		format!("measures {}-{}", measures.start + 1, measures.end)
+	};
+	let meter_text = chart
+		.meter
+		.map_or_else(String::new, |meter| format!(" {}", meter));
+	poise::send_reply(ctx, |f| {
+		f.embed(|e| {
+			e.color(crate::ETTERNA_COLOR)
+				.title(format!(
+					"{} - {}",
+					library_chart.artist, library_chart.title
+				))
+				.description(format!(
+					"{}{} ({}), {}",
+					chart.difficulty, meter_text, chart.steps_type, measures_text
+				))
+				.attachment("chart.png")
+		})
+		.attachment(serenity::AttachmentType::Bytes {
+			data: img_bytes.into(),
+			filename: "chart.png".to_owned(),
+		})
+	})
+	.await?;
+
+	Ok(())
+}
@@ -0,0 +1,185 @@
+//! Looks up charts by chartkey or song name in the chart library directory from the config.
+//! Only .sm and .ssc files are read, and chartkeys are only known from a hand-written index file
+
+use crate::{Error, Warn as _};
+use ::pattern as pattern_draw;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+static CHARTKEY_INDEX_FILE_NAME: &str = "chartkeys.json";
+
+/// Where to find the chart with a certain chartkey. Etterna computes chartkeys from the note data
+/// and BPMs in a way we don't replicate, so they're listed in an index file instead
+#[derive(Deserialize)]
+struct ChartkeyIndexEntry {
+	/// Path of the .sm or .ssc file, relative to the library directory
+	file: PathBuf,
+	/// Difficulty name of the chart inside the file, like `Challenge`
+	difficulty: String,
+}
+
+struct LibrarySong {
+	path: PathBuf,
+	title: String,
+	artist: String,
+}
+
+pub struct LibraryChart {
+	pub title: String,
+	pub artist: String,
+	pub chart: pattern_draw::SimfileChart,
+}
+
+/// Make lowercase and remove all special characters, so that song names match even if the user
+/// doesn't type the punctuation
+fn normalize_song_name(name: &str) -> String {
+	let mut normalized_name = name.to_lowercase();
+	normalized_name.retain(|c| c.is_alphanumeric());
+	normalized_name
+}
+
+fn collect_simfiles(directory: &Path, simfiles: &mut Vec<PathBuf>) {
+	for entry in std::fs::read_dir(directory).warn().into_iter().flatten() {
+		let path = match entry.warn() {
+			Some(entry) => entry.path(),
+			None => continue,
+		};
+		if path.is_dir() {
+			collect_simfiles(&path, simfiles);
+		} else if matches!(
+			path.extension().and_then(|ext| ext.to_str()),
+			Some("sm" | "ssc")
+		) {
+			simfiles.push(path);
+		}
+	}
+}
+
+fn read_simfile(path: &Path) -> Result<pattern_draw::Simfile, Error> {
+	// Old simfiles are often not UTF-8
+	let bytes = std::fs::read(path)?;
+	Ok(pattern_draw::parse_simfile(&String::from_utf8_lossy(
+		&bytes,
+	)))
+}
+
+pub struct ChartLibrary {
+	directory: Option<PathBuf>,
+	songs: Vec<LibrarySong>,
+	chartkeys: HashMap<String, ChartkeyIndexEntry>,
+}
+
+impl ChartLibrary {
+	/// Reads the title and artist of every simfile in the directory. Files that fail to load are
+	/// skipped with a warning
+	pub fn load(directory: Option<&Path>) -> Self {
+		let directory = match directory {
+			Some(directory) => directory,
+			None => {
+				return Self {
+					directory: None,
+					songs: Vec::new(),
+					chartkeys: HashMap::new(),
+				}
+			}
+		};
+
+		let mut simfile_paths = Vec::new();
+		collect_simfiles(directory, &mut simfile_paths);
+		// read_dir doesn't guarantee any order, but we want search results to be stable
+		simfile_paths.sort();
+
+		let songs = simfile_paths
+			.into_iter()
+			.filter_map(|path| {
+				let simfile = read_simfile(&path).warn()?;
+				Some(LibrarySong {
+					path,
+					title: simfile.title,
+					artist: simfile.artist,
+				})
+			})
+			.collect::<Vec<_>>();
+
+		let index_path = directory.join(CHARTKEY_INDEX_FILE_NAME);
+		let chartkeys = if index_path.is_file() {
+			std::fs::read_to_string(&index_path)
+				.map_err(Error::from)
+				.and_then(|index| Ok(serde_json::from_str(&index)?))
+				.warn()
+				.unwrap_or_default()
+		} else {
+			HashMap::new()
+		};
+
+		log::info!(
+			"Loaded {} songs and {} chartkeys from the chart library",
+			songs.len(),
+			chartkeys.len()
+		);
+		Self {
+			directory: Some(directory.to_owned()),
+			songs,
+			chartkeys,
+		}
+	}
+
+	/// Finds a chart by its chartkey, or the hardest chart of the song whose title best matches
+	/// the query. Chartkeys that aren't in the index file are treated like song names
+	pub fn find(&self, query: &str) -> Result<LibraryChart, Error> {
+		let directory = self
+			.directory
+			.as_ref()
+			.ok_or_else(|| anyhow::anyhow!("No chart library is configured"))?;
+
+		if let Some(entry) = self.chartkeys.get(query) {
+			let simfile = read_simfile(&directory.join(&entry.file))?;
+			let chart = simfile
+				.charts
+				.into_iter()
+				.find(|chart| chart.difficulty.eq_ignore_ascii_case(&entry.difficulty))
+				.ok_or_else(|| {
+					anyhow::anyhow!(
+						"{:?} doesn't contain a {} chart",
+						entry.file,
+						entry.difficulty
+					)
+				})?;
+			return Ok(LibraryChart {
+				title: simfile.title,
+				artist: simfile.artist,
+				chart,
+			});
+		}
+
+		let no_chart_found = || anyhow::anyhow!("No chart found with that chartkey or song name");
+		let query = normalize_song_name(query);
+		// An empty string would be contained in every title
+		if query.is_empty() {
+			return Err(no_chart_found());
+		}
+		let song = self
+			.songs
+			.iter()
+			.find(|song| normalize_song_name(&song.title) == query)
+			.or_else(|| {
+				self.songs
+					.iter()
+					.find(|song| normalize_song_name(&song.title).contains(&query))
+			})
+			.ok_or_else(no_chart_found)?;
+
+		let simfile = read_simfile(&song.path)?;
+		let chart = simfile
+			.charts
+			.into_iter()
+			.max_by_key(|chart| chart.meter)
+			.ok_or_else(|| anyhow::anyhow!("{} doesn't contain any charts", song.title))?;
+		Ok(LibraryChart {
+			title: song.title.clone(),
+			artist: song.artist.clone(),
+			chart,
+		})
+	}
+}
@@ -15,18 +15,52 @@ pub async fn help(
 
 pub async fn send_help(ctx: Context<'_>, pattern: bool) -> Result<(), Error> {
 	let embed_contents = if pattern {
-		r#"
-**+pattern [down/up] [NN]ths [noteskin] [zoom]x [keymode]k PATTERN STRING**
+		let noteskins = ctx
+			.data()
+			.noteskin_provider
+			.names()
+			.map(|(name, aliases)| {
+				std::iter::once(name)
+					.chain(aliases.iter().map(|alias| alias.as_str()))
+					.map(|name| format!("`{}`", name))
+					.collect::<Vec<_>>()
+					.join("/")
+			})
+			.collect::<Vec<_>>();
+		let noteskins = match noteskins.split_last() {
+			Some((last, [])) => last.clone(),
+			Some((last, others)) => format!("{}, or {}", others.join(", "), last),
+			None => "nothing, because no noteskins are installed".to_owned(),
+		};
+
+		format!(
+			r#"
+**+pattern [down/up] [NN]ths [noteskin] [zoom]x [keymode]k [animate [NNN]bpm] [analyze] [export] [svg] [grid] [hands] [rhythm] [tint] [modifiers] PATTERN STRING**
 - `down/up` configures the scroll direction (note: you can set your default with `+scrollset`)
 - `NNths` (e.g. `20ths`) sets the note snap. Can be used mid-pattern
-- `noteskin` can be `delta-note`, `sbz`/`subtract-by-zero`, `dbz`/`divide-by-zero`, `mbz`/`multiply-by-zero`, `lambda`, or `wafles`/`wafles3`[.](https://pastebin.com/raw/VSKisWbM)
+- `noteskin` can be {}[.](https://pastebin.com/raw/VSKisWbM)
 - `zoom` (e.g. `2x`) applies a certain stretch to the notes
 - `keymode` (e.g. `5k` can be used to force a certain keymode when it's not obvious
+- `animate` renders an animation of the pattern scrolling by at the given BPM (e.g. `animate 190bpm`). Zoom changes the scroll speed
+- `analyze` adds statistics about the pattern, like chord density, jacks, trills and anchors, and guesses what skillset it is
+- `export` attaches the pattern as .sm note data and as ArrowVortex clipboard data, ready to paste into the editor
+- `svg` sends the pattern as a vector image instead, which isn't limited in size and stays sharp when zoomed in. Useful for very long patterns
+- `grid` draws beat lines, beat numbers and a legend of the snaps next to the pattern
+- `hands` tints the lanes of the left and right hand, and marks jacks with red brackets and minijacks with yellow ones
+- `rhythm` colors notes by the snap they're written in, instead of by their position in the beat
+- `tint` draws plain notes tinted with the snap color, so that every snap gets its own color, even 20ths
+- `modifiers` change the lanes of the notes, applied in the given order: `mirror`, `flip` (swap hands), `rotate` or `rotate:N`, `shuffle` or `shuffle:SEED` (the same seed always shuffles the same way), `nojacks` and `convert:6k` (spread the lanes across another keymode)
 
 To draw a chord, enclose the notes in bracketes: `[12][34][12][34]` creates a jumptrill.
+Patterns too long for one image are split into columns of whole measures.
+To compare patterns, separate them with `|`: `+pattern [12]3[24]1 | mirror [12]3[24]1` draws them next to each other. Snaps and modifiers apply to the pattern they're written in, all other options to every pattern.
 Empty rows are written with `0` or `[]`.
 Lane numbers beyond 9 must be enclosed in paranthesis: `123456789(10)` instead of `12345678910`.
 Insert `M` to switch to mine mode for the current note row.
+Append `x` and a row count to draw a hold: `1x4` holds lane 1 for four rows, `[12]x4` holds a jump.
+Repeat a part of the pattern with `{{...}}*N`: `{{[12][34]}}*16` draws a jumptrill of 32 rows.
+Save a part of the pattern as a macro with `<name>={{...}}`, then draw it with `<name>` or `<name>*N`: `<jt>={{[12][34]}} <jt>*8 1234 <jt>*8`.
+You can also paste note data copied from ArrowVortex, or the measures of a .sm/.ssc file (`0000`, `1000`, ... separated by `,`).
 
 Examples:
 `+pattern [13]4[32]1[24]1[23]4` draws a simple jumpstream
@@ -34,7 +68,11 @@ Examples:
 `+pattern 2x 12ths 123432 16ths 1313` draws a few 12ths notes, followed by a 16ths trill, all stretched by a factor of 2
 `+pattern 57ths 123432123412341234123` creates funny colors
 `+pattern 6k [34]52[34]25` draws a pattern in 6k mode, even though the notes span across just 5 lanes
-			"#.to_owned()
+
+Tired of typing the same options every time? Save your defaults for noteskin, snap, zoom and scroll direction with `+patternset`, e.g. `+patternset lambda 2x down`. Write a keymode before a noteskin to use it in that keymode only: `+patternset 6k dbz`
+			"#,
+			noteskins
+		)
 	} else {
 		use rand::seq::SliceRandom as _;
 
@@ -66,10 +104,18 @@ Here are my commands: (Descriptions by Fission)
 More commands:
 **+pattern [pattern string]**
 *Visualize note patterns, for example `lrlr` or `[14]3[12]`. This command has many options, type `+help pattern` for that*
+**+patternset [options]**
+*Save your default noteskin, snap, zoom and scroll direction for +pattern*
+**+chart [chartkey/song name] [measures 12-20]**
+*Show a section of a chart from the bot's chart library. Chartkeys only work for charts the library lists in its chartkeys.json*
+**+density [pattern string] [NNN]bpm**
+*Graph the notes per second of a pattern or .sm note data, in total, chord-weighted and for each hand*
 **+skillgraph [user] [user 2] [...]** or **+accuracygraph [user]**
 *Show a graph of your profile rating over time*
 **+rs [username] [judge]**
-*Show your most recent score*
+*Show your most recent score. The judge can be e.g. `J7`, `J4.5`, `15ms`, or `all` for a table of every judge*
+**+replay [bpm] [judge]** with a file from Etterna's ReplaysV2 folder attached
+*Analyze a score that isn't on EO. The BPM of the chart is needed for correct tap speeds*
 **+quote**
 *Print one of various random quotes, phrases and memes from various rhythm gaming communities ([Credit](https://github.com/ca25nada/spawncamping-wallhack/blob/master/Scripts/Quotes.lua))*
 **+lastsession [username]**
@@ -79,7 +125,8 @@ More commands:
 **+aroundme [username] [skillset] [amount]**
 *Show surrounding leaderboard entries*
 
-You can also post links to scores and I will show info about them. If you add a judge (e.g. "J7") to your message, I will also show the wifescores with that judge.
+You can also post links to scores and I will show info about them. If you add a judge (e.g. "J7", "J4.5", or timing windows like "judge 15ms" or "windows 22.5/45/90/135/180ms") to your message, I will also show the wifescores with that judge. Write "all judges" to see the score on every judge from J1 to J9.
+Replay files from Etterna's ReplaysV2 folder that you post are analyzed the same way.
 If you edit your message, the bot will update its response.
 				"#,
 			minanym,
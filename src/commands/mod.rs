@@ -15,6 +15,15 @@ pub use leaderboard::*;
 mod pattern;
 pub use self::pattern::*;
 
+mod noteskin_provider;
+pub use noteskin_provider::*;
+
+mod chart;
+pub use chart::*;
+
+mod chart_library;
+pub use chart_library::*;
+
 mod skill_graph;
 pub use skill_graph::*;
 
@@ -0,0 +1,329 @@
+//! Loads the noteskins for the pattern command from `assets/noteskin/*/noteskin.json`
+
+use crate::{Error, Warn as _};
+use ::pattern as pattern_draw;
+use serde::Deserialize;
+use std::path::Path;
+
+static NOTESKIN_DIRECTORY: &str = "assets/noteskin";
+static MANIFEST_FILE_NAME: &str = "noteskin.json";
+
+/// Texture paths for each noteskin layout, relative to the manifest
+#[derive(Deserialize)]
+#[serde(tag = "layout", rename_all = "lowercase")]
+enum NoteskinLayout {
+	Ldur6k {
+		notes: String,
+		receptor: String,
+		mine: String,
+	},
+	Ldur {
+		left_note: String,
+		left_receptor: String,
+		down_note: String,
+		down_receptor: String,
+		up_note: String,
+		up_receptor: String,
+		right_note: String,
+		right_receptor: String,
+		mine: String,
+	},
+	Pump {
+		center_notes: String,
+		center_receptor: String,
+		corner_notes: String,
+		corner_receptor: String,
+		mine: String,
+	},
+	Bar {
+		notes: String,
+		receptor: String,
+		mine: String,
+	},
+}
+
+impl NoteskinLayout {
+	/// Texture maps which are cut into sprites of the manifest's sprite resolution
+	fn texture_files(&self) -> Vec<&String> {
+		match self {
+			Self::Ldur6k {
+				notes,
+				receptor,
+				mine,
+			}
+			| Self::Bar {
+				notes,
+				receptor,
+				mine,
+			} => vec![notes, receptor, mine],
+			Self::Ldur {
+				left_note,
+				left_receptor,
+				down_note,
+				down_receptor,
+				up_note,
+				up_receptor,
+				right_note,
+				right_receptor,
+				mine,
+			} => vec![
+				left_note,
+				left_receptor,
+				down_note,
+				down_receptor,
+				up_note,
+				up_receptor,
+				right_note,
+				right_receptor,
+				mine,
+			],
+			Self::Pump {
+				center_notes,
+				center_receptor,
+				corner_notes,
+				corner_receptor,
+				mine,
+			} => vec![
+				center_notes,
+				center_receptor,
+				corner_notes,
+				corner_receptor,
+				mine,
+			],
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct NoteskinManifest {
+	name: String,
+	#[serde(default)]
+	aliases: Vec<String>,
+	#[serde(flatten)]
+	layout: NoteskinLayout,
+	/// Size of a single sprite in the texture files
+	sprite_resolution: usize,
+	/// Sprite size to scale the textures to after loading
+	#[serde(default)]
+	resize_to: Option<u32>,
+	/// Whether the textures were drawn upside down
+	#[serde(default)]
+	upside_down: bool,
+	hold_body: Option<String>,
+	hold_tail: Option<String>,
+	roll_body: Option<String>,
+	roll_tail: Option<String>,
+	/// Keymodes in which this noteskin is used if the user didn't choose one
+	#[serde(default)]
+	default_for_keymodes: Vec<u32>,
+	/// Whether this noteskin is used for keymodes which no noteskin is the default for
+	#[serde(default)]
+	default_for_other_keymodes: bool,
+}
+
+struct RegisteredNoteskin {
+	name: String,
+	aliases: Vec<String>,
+	default_for_keymodes: Vec<u32>,
+	default_for_other_keymodes: bool,
+	noteskin: pattern_draw::Noteskin,
+}
+
+/// Make lowercase and remove all special characters, so that e.g. `Divide-By-Zero` matches
+/// `dividebyzero`
+fn normalize_noteskin_name(name: &str) -> String {
+	let mut normalized_name = name.to_ascii_lowercase();
+	normalized_name.retain(|c| c.is_alphanumeric());
+	normalized_name
+}
+
+fn load_noteskin(noteskin_dir: &Path) -> Result<RegisteredNoteskin, Error> {
+	let manifest_path = noteskin_dir.join(MANIFEST_FILE_NAME);
+	let manifest: NoteskinManifest =
+		serde_json::from_str(&std::fs::read_to_string(&manifest_path)?)
+			.map_err(|e| anyhow::anyhow!("Invalid noteskin manifest {:?}: {}", manifest_path, e))?;
+
+	let invalid_manifest = |reason: String| {
+		anyhow::anyhow!("Invalid noteskin manifest {:?}: {}", manifest_path, reason)
+	};
+	if manifest.sprite_resolution == 0 || manifest.resize_to == Some(0) {
+		return Err(invalid_manifest("sprite sizes must be positive".into()));
+	}
+	if manifest.hold_body.is_some() != manifest.hold_tail.is_some() {
+		return Err(invalid_manifest(
+			"hold_body and hold_tail must be given together".into(),
+		));
+	}
+	if manifest.roll_body.is_some() != manifest.roll_tail.is_some() {
+		return Err(invalid_manifest(
+			"roll_body and roll_tail must be given together".into(),
+		));
+	}
+	if manifest.roll_body.is_some() && manifest.hold_body.is_none() {
+		return Err(invalid_manifest(
+			"roll textures need hold textures as well".into(),
+		));
+	}
+
+	// Missing textures would be replaced by small placeholders, which can't be cut into sprites
+	for file_name in manifest.layout.texture_files() {
+		let (width, height) = image::image_dimensions(noteskin_dir.join(file_name))
+			.map_err(|e| invalid_manifest(format!("can't read {}: {}", file_name, e)))?;
+		if (width.min(height) as usize) < manifest.sprite_resolution {
+			return Err(invalid_manifest(format!(
+				"{} is smaller than the sprite resolution",
+				file_name
+			)));
+		}
+	}
+
+	let path = |file_name: &str| noteskin_dir.join(file_name).to_string_lossy().into_owned();
+	let res = manifest.sprite_resolution;
+	let mut noteskin = match &manifest.layout {
+		NoteskinLayout::Ldur6k {
+			notes,
+			receptor,
+			mine,
+		} => pattern_draw::Noteskin::read_ldur_with_6k(
+			res,
+			&path(notes),
+			&path(receptor),
+			&path(mine),
+		),
+		NoteskinLayout::Ldur {
+			left_note,
+			left_receptor,
+			down_note,
+			down_receptor,
+			up_note,
+			up_receptor,
+			right_note,
+			right_receptor,
+			mine,
+		} => pattern_draw::Noteskin::read_ldur(
+			res,
+			&path(left_note),
+			&path(left_receptor),
+			&path(down_note),
+			&path(down_receptor),
+			&path(up_note),
+			&path(up_receptor),
+			&path(right_note),
+			&path(right_receptor),
+			&path(mine),
+		),
+		NoteskinLayout::Pump {
+			center_notes,
+			center_receptor,
+			corner_notes,
+			corner_receptor,
+			mine,
+		} => pattern_draw::Noteskin::read_pump(
+			res,
+			&path(center_notes),
+			&path(center_receptor),
+			&path(corner_notes),
+			&path(corner_receptor),
+			&path(mine),
+		),
+		NoteskinLayout::Bar {
+			notes,
+			receptor,
+			mine,
+		} => pattern_draw::Noteskin::read_bar(res, &path(notes), &path(receptor), &path(mine)),
+	};
+
+	if let (Some(hold_body), Some(hold_tail)) = (&manifest.hold_body, &manifest.hold_tail) {
+		noteskin.read_hold_textures(
+			&path(hold_body),
+			&path(hold_tail),
+			manifest.roll_body.as_deref().map(path).as_deref(),
+			manifest.roll_tail.as_deref().map(path).as_deref(),
+		);
+	}
+	if let Some(sprite_resolution) = manifest.resize_to {
+		noteskin.resize_sprites(sprite_resolution);
+	}
+	if manifest.upside_down {
+		noteskin.turn_sprites_upside_down();
+	}
+
+	Ok(RegisteredNoteskin {
+		name: manifest.name,
+		aliases: manifest.aliases,
+		default_for_keymodes: manifest.default_for_keymodes,
+		default_for_other_keymodes: manifest.default_for_other_keymodes,
+		noteskin,
+	})
+}
+
+pub struct NoteskinProvider {
+	noteskins: Vec<RegisteredNoteskin>,
+}
+
+impl NoteskinProvider {
+	/// Loads every subdirectory of the noteskin directory that contains a manifest. Noteskins that
+	/// fail to load are skipped with a warning
+	pub fn load() -> Self {
+		let mut noteskin_dirs = std::fs::read_dir(NOTESKIN_DIRECTORY)
+			.warn()
+			.into_iter()
+			.flatten()
+			.filter_map(|entry| Some(entry.warn()?.path()))
+			.filter(|path| path.join(MANIFEST_FILE_NAME).is_file())
+			.collect::<Vec<_>>();
+		// read_dir doesn't guarantee any order, but we want the noteskin list to be stable
+		noteskin_dirs.sort();
+
+		let noteskins = noteskin_dirs
+			.iter()
+			.filter_map(|noteskin_dir| load_noteskin(noteskin_dir).warn())
+			.collect::<Vec<_>>();
+		if noteskins.is_empty() {
+			log::warn!("No noteskins found in {}", NOTESKIN_DIRECTORY);
+		}
+
+		Self { noteskins }
+	}
+
+	fn find(&self, name: &str) -> Option<&RegisteredNoteskin> {
+		let name = normalize_noteskin_name(name);
+		self.noteskins.iter().find(|skin| {
+			std::iter::once(&skin.name)
+				.chain(&skin.aliases)
+				.any(|skin_name| normalize_noteskin_name(skin_name) == name)
+		})
+	}
+
+	/// Looks up a noteskin by its name or one of its aliases, ignoring case and special characters
+	pub fn get(&self, name: &str) -> Option<&pattern_draw::Noteskin> {
+		self.find(name).map(|skin| &skin.noteskin)
+	}
+
+	/// Turns an alias or differently spelled noteskin name into the name from the manifest
+	pub fn canonical_name(&self, name: &str) -> Option<&str> {
+		self.find(name).map(|skin| skin.name.as_str())
+	}
+
+	/// The noteskin used when the user didn't choose one. Returns None only if there are no
+	/// noteskins at all
+	pub fn default_for_keymode(&self, keymode: u32) -> Option<&pattern_draw::Noteskin> {
+		self.noteskins
+			.iter()
+			.find(|skin| skin.default_for_keymodes.contains(&keymode))
+			.or_else(|| {
+				self.noteskins
+					.iter()
+					.find(|skin| skin.default_for_other_keymodes)
+			})
+			.or_else(|| self.noteskins.first())
+			.map(|skin| &skin.noteskin)
+	}
+
+	/// Name and aliases of each noteskin, for the help text
+	pub fn names(&self) -> impl Iterator<Item = (&str, &[String])> {
+		self.noteskins
+			.iter()
+			.map(|skin| (skin.name.as_str(), skin.aliases.as_slice()))
+	}
+}
@@ -5,373 +5,1031 @@ use ::pattern as pattern_draw;
 pub use pattern_draw::{Error as PatternError, Noteskin};
 use poise::serenity_prelude as serenity;
 
-pub struct NoteskinProvider {
-	dbz: pattern_draw::Noteskin,
-	lambda: pattern_draw::Noteskin,
-	wafles: pattern_draw::Noteskin,
-	delta_note: pattern_draw::Noteskin,
-	sbz: pattern_draw::Noteskin,
-	mbz: pattern_draw::Noteskin,
-	eo_baner: pattern_draw::Noteskin,
-	rustmania: pattern_draw::Noteskin,
-}
-
-impl NoteskinProvider {
-	pub fn load() -> Self {
-		Self {
-			dbz: Noteskin::read_ldur_with_6k(
-				64,
-				"assets/noteskin/dbz-notes.png",
-				"assets/noteskin/dbz-receptor.png",
-				"assets/noteskin/dbz-mine.png",
-			),
-			delta_note: Noteskin::read_pump(
-				64,
-				"assets/noteskin/deltanote-center-notes.png",
-				"assets/noteskin/deltanote-center-receptor.png",
-				"assets/noteskin/deltanote-corner-notes.png",
-				"assets/noteskin/deltanote-corner-receptor.png",
-				"assets/noteskin/deltanote-mine.png",
-			),
-			sbz: Noteskin::read_bar(
-				64,
-				"assets/noteskin/sbz-notes.png",
-				"assets/noteskin/sbz-receptor.png",
-				"assets/noteskin/dbz-mine.png",
-			),
-			mbz: Noteskin::read_bar(
-				64,
-				"assets/noteskin/mbz-notes.png",
-				"assets/noteskin/mbz-receptor.png",
-				"assets/noteskin/dbz-mine.png",
-			),
-			lambda: {
-				let mut lambda = Noteskin::read_ldur_with_6k(
-					128,
-					"assets/noteskin/lambda-notes.png",
-					"assets/noteskin/lambda-receptor.png",
-					"assets/noteskin/lambda-mine.png",
-				);
-				lambda.resize_sprites(64);
-				lambda
-			},
-			wafles: Noteskin::read_ldur_with_6k(
-				64,
-				"assets/noteskin/wafles-notes.png",
-				"assets/noteskin/wafles-receptor.png",
-				"assets/noteskin/wafles-mine.png",
-			),
-			eo_baner: Noteskin::read_ldur(
-				120,
-				"assets/noteskin/eobaner-note-left.png",
-				"assets/noteskin/eobaner-receptor-left.png",
-				"assets/noteskin/eobaner-note-down.png",
-				"assets/noteskin/eobaner-receptor-down.png",
-				"assets/noteskin/eobaner-note-up.png",
-				"assets/noteskin/eobaner-receptor-up.png",
-				"assets/noteskin/eobaner-note-right.png",
-				"assets/noteskin/eobaner-receptor-right.png",
-				"assets/noteskin/eobaner-mine.png",
-			),
-			rustmania: {
-				let mut rustmania = Noteskin::read_ldur_with_6k(
-					224,
-					"assets/noteskin/rustmania-notes.png",
-					"assets/noteskin/rustmania-receptor.png",
-					"assets/noteskin/rustmania-mine.png",
-				);
-				rustmania.turn_sprites_upside_down(); // I made an oopsie in gimp
-				rustmania
-			},
-		}
-	}
-}
+/// Limit for the number of sprites in a pattern image, and for the number of rows that a pattern
+/// may expand to
+const MAX_SPRITES: usize = 1000;
+/// Limit for the number of rows in an ArrowVortex pattern. Its rows are 192nds, so this is as long
+/// as MAX_SPRITES rows of 16ths in the regular pattern syntax
+const MAX_ARROWVORTEX_ROWS: usize = MAX_SPRITES * 12;
+/// Discord rejects bigger attachments for bots in servers without boosts
+const DISCORD_ATTACHMENT_SIZE_LIMIT: usize = 8_000_000;
 
 async fn always_true(_: Context<'_>) -> Result<bool, Error> {
 	Ok(true)
 }
 
-/// Visualize note patterns
-#[poise::command(prefix_command, slash_command, track_edits, check = "always_true")]
-pub async fn pattern(
-	ctx: Context<'_>,
-	#[rest]
-	#[description = "Pattern string to render"]
-	pattern: String,
-) -> Result<(), Error> {
-	// People are supposed to write `+help pattern` but some write `+pattern help` so let's help
-	// them as well :)
-	if pattern.eq_ignore_ascii_case("help") {
-		super::help::send_help(ctx, true).await?;
-		return Ok(());
-	}
+fn convert_arrowvortex_note_kind<P>(
+	kind: arrowvortex_clipboard::NoteKind<P>,
+	hold_length: impl FnOnce(P) -> Result<u32, Error>,
+) -> Result<pattern_draw::NoteType, Error> {
+	Ok(match kind {
+		arrowvortex_clipboard::NoteKind::Tap => pattern_draw::NoteType::Tap,
+		arrowvortex_clipboard::NoteKind::Mine => pattern_draw::NoteType::Mine,
+		arrowvortex_clipboard::NoteKind::Hold { end_pos } => pattern_draw::NoteType::Hold {
+			length: hold_length(end_pos)?,
+		},
+		arrowvortex_clipboard::NoteKind::Roll { end_pos } => pattern_draw::NoteType::Roll {
+			length: hold_length(end_pos)?,
+		},
+		arrowvortex_clipboard::NoteKind::Lift => pattern_draw::NoteType::Lift,
+		arrowvortex_clipboard::NoteKind::Fake => pattern_draw::NoteType::Fake,
+	})
+}
 
-	let mut noteskin_override = None;
-	let mut keymode_override = None;
-	let mut snap = etterna::Snap::_16th.into();
-	let mut vertical_spacing_multiplier = 1.0;
-	let mut scroll_direction = ctx
-		.data()
-		.lock_data()
-		.scroll(ctx.author().id)
-		.unwrap_or(etterna::ScrollDirection::Upscroll);
-	let mut segments = Vec::new();
+/// Parses one or more ArrowVortex clipboard strings into a pattern where every row is a 192nd.
+/// Time-based notes are quantized onto rows, using the BPM from pasted tempo data if there is
+/// any. If the quantization had to approximate, a notice for the user is returned as well
+fn parse_arrowvortex_pattern(
+	full_pattern: &str,
+) -> Result<(pattern_draw::Pattern, Option<String>), Error> {
+	let mut row_based_notes = Vec::new();
+	let mut time_based_notes = Vec::new();
+	let mut tempo_events = Vec::new();
 
-	let extract_snap = |string: &str, user_intended: &mut bool| {
-		const ENDINGS: &[&str] = &["st", "sts", "nd", "nds", "rd", "rds", "th", "ths"];
+	let mut clipboard_starts = full_pattern
+		.match_indices("ArrowVortex:")
+		.map(|(i, _)| i)
+		.collect::<Vec<_>>();
+	clipboard_starts.push(full_pattern.len());
+	for bounds in clipboard_starts.windows(2) {
+		// Unwrap code block ticks
+		let clipboard = full_pattern[bounds[0]..bounds[1]].trim_end_matches('`');
 
-		let characters_to_truncate = ENDINGS
-			.iter()
-			.find(|&ending| string.ends_with(ending))?
-			.len();
-		// UNWRAP: we're only removing up to the string length, so we can't go out-of-bounds
-		let string_without_ending = string
-			.get(..(string.len() - characters_to_truncate))
-			.unwrap();
-		let snap: u32 = string_without_ending.parse().ok()?;
-		*user_intended = true;
-		pattern_draw::FractionalSnap::from_snap_number(snap)
-	};
-	let extract_noteskin = |string: &str, _user_intended: &mut bool| {
-		// make lowercase and remove all special characters
-		let mut normalized_noteskin_name = string.to_ascii_lowercase();
-		normalized_noteskin_name.retain(|c| c.is_alphanumeric());
-
-		match normalized_noteskin_name.as_str() {
-			"dbz" | "dividebyzero" => Some(&ctx.data().noteskin_provider.dbz),
-			"wafles" | "wafles3" => Some(&ctx.data().noteskin_provider.wafles),
-			"default" | "lambda" => Some(&ctx.data().noteskin_provider.lambda),
-			"deltanote" | "delta" => Some(&ctx.data().noteskin_provider.delta_note),
-			"sbz" | "subtractbyzero" => Some(&ctx.data().noteskin_provider.sbz),
-			"mbz" | "multiplybyzero" => Some(&ctx.data().noteskin_provider.mbz),
-			"eobaner" => Some(&ctx.data().noteskin_provider.eo_baner),
-			"rustmania" => Some(&ctx.data().noteskin_provider.rustmania),
-			_ => None,
+		match arrowvortex_clipboard::decode(clipboard.as_bytes())
+			.map_err(|e| anyhow::anyhow!("Failed to decode ArrowVortex pattern: {}", e))?
+		{
+			arrowvortex_clipboard::DecodeResult::RowBasedNotes(notes) => {
+				row_based_notes.extend(notes)
+			}
+			arrowvortex_clipboard::DecodeResult::TimeBasedNotes(notes) => {
+				time_based_notes.extend(notes)
+			}
+			arrowvortex_clipboard::DecodeResult::TempoEvents(events) => tempo_events.extend(events),
 		}
+	}
+
+	let too_long = || PatternError::PatternTooLong {
+		limit: MAX_ARROWVORTEX_ROWS,
 	};
-	let extract_vertical_spacing_multiplier = |string: &str, user_intended: &mut bool| {
-		if !string.ends_with('x') {
-			return None;
-		};
-		// UNWRAP: at this point the string must have 'x' at the end so we can safely strip one char
-		let vertical_spacing_multiplier: f32 =
-			string.get(..(string.len() - 1)).unwrap().parse().ok()?;
-		*user_intended = true;
-		if vertical_spacing_multiplier > 0.0 {
-			Some(vertical_spacing_multiplier)
-		} else {
-			None
+	// Hold lengths include the row of the hold head
+	let hold_length = |pos: i32, end_pos: i32| -> Result<u32, PatternError> {
+		let length = end_pos
+			.checked_sub(pos)
+			.and_then(|length| length.checked_add(1))
+			.ok_or_else(too_long)?
+			.max(1) as u32;
+		if length as usize > MAX_ARROWVORTEX_ROWS {
+			return Err(too_long());
 		}
+		Ok(length)
 	};
-	let extract_scroll_direction =
-		|string: &str, _user_intended: &mut bool| match string.to_lowercase().as_str() {
-			"up" => Some(etterna::ScrollDirection::Upscroll),
-			"down" | "reverse" => Some(etterna::ScrollDirection::Downscroll),
-			_ => None,
-		};
-	let extract_keymode = |string: &str, user_intended: &mut bool| {
-		if !(string.ends_with('k') || string.ends_with('K')) {
-			return None;
-		}
 
-		// UNWRAP: at this point the string must have 'k' at the end so we can safely strip one char
-		let keymode: u32 = string.get(..(string.len() - 1)).unwrap().parse().ok()?;
-		*user_intended = true;
-		if keymode > 0 {
-			Some(keymode)
-		} else {
-			None
+	let mut notice = None;
+	let mut notes = Vec::new();
+	if !row_based_notes.is_empty() && !time_based_notes.is_empty() {
+		return Err(anyhow::anyhow!(
+			"Please paste either row-based or time-based notes, not both"
+		));
+	} else if !row_based_notes.is_empty() {
+		for note in row_based_notes {
+			let pos = note.pos;
+			let note_type =
+				convert_arrowvortex_note_kind(note.kind, |end_pos| Ok(hold_length(pos, end_pos)?))?;
+			notes.push((pos, note.column, note_type));
 		}
-	};
+	} else if !time_based_notes.is_empty() {
+		// Tempo data is expected to be copied from the same selection as the notes, so its
+		// first event lines up with the first note
+		let first_tempo_event_pos = tempo_events.iter().map(|event| event.pos).min();
+		let bpm_changes = tempo_events
+			.iter()
+			.filter_map(|event| match event.kind {
+				arrowvortex_clipboard::TempoEventKind::Bpm { bpm } => {
+					Some(pattern_draw::BpmChange {
+						row: (event.pos - first_tempo_event_pos?) as u32,
+						bpm,
+					})
+				}
+				_ => None,
+			})
+			.collect::<Vec<_>>();
 
-	let mut pattern_buffer = String::new();
-	for arg in pattern.split_whitespace() {
-		let mut did_user_intend = false;
-		if let Some(new_snap) = extract_snap(arg, &mut did_user_intend) {
-			if pattern_buffer.len() > 0 {
-				segments.push((pattern_buffer.clone(), snap));
-				pattern_buffer.clear();
+		let mut times = Vec::new();
+		for note in &time_based_notes {
+			times.push(note.pos);
+			if let arrowvortex_clipboard::NoteKind::Hold { end_pos }
+			| arrowvortex_clipboard::NoteKind::Roll { end_pos } = note.kind
+			{
+				times.push(end_pos);
 			}
-			snap = new_snap;
-			continue;
 		}
-		if did_user_intend {
-			poise::say_reply(ctx, format!("\"{}\" is not a valid snap", arg)).await?;
+		let quantization =
+			pattern_draw::quantize_times(&times, &bpm_changes).ok_or(PatternError::EmptyPattern)?;
+		// Hold ends are quantized too, so this bounds the hold lengths as well
+		if quantization
+			.rows
+			.iter()
+			.any(|&row| row as usize >= MAX_ARROWVORTEX_ROWS)
+		{
+			return Err(too_long().into());
 		}
 
-		let mut did_user_intend = false;
-		if let Some(noteskin) = extract_noteskin(arg, &mut did_user_intend) {
-			noteskin_override = Some(noteskin);
-			continue;
+		// Rows are in the same order as the timestamps we pushed above, so there should be a row
+		// for every note plus one for every hold end
+		let missing_row = || anyhow::anyhow!("Quantization returned fewer rows than timestamps");
+		let mut rows = quantization.rows.iter().map(|&row| row as i32);
+		for note in time_based_notes {
+			let pos = rows.next().ok_or_else(missing_row)?;
+			let note_type = convert_arrowvortex_note_kind(note.kind, |_| {
+				Ok(hold_length(pos, rows.next().ok_or_else(missing_row)?)?)
+			})?;
+			notes.push((pos, note.column, note_type));
 		}
-		if did_user_intend {
-			poise::say_reply(ctx, format!("\"{}\" is not a valid noteskin name", arg)).await?;
+
+		if quantization.num_approximated > 0 {
+			notice = Some(format!(
+				"{} of {} timestamps didn't fit on a common snap at {:.0} BPM{} and were rounded \
+				to the nearest 192nd (off by up to {:.1}ms)",
+				quantization.num_approximated,
+				times.len(),
+				quantization.bpm,
+				if quantization.bpm_was_estimated {
+					" (estimated; paste tempo data along with the notes for better results)"
+				} else {
+					""
+				},
+				quantization.max_error * 1000.0,
+			));
 		}
+	} else {
+		return Err(anyhow::anyhow!("Please paste note data, not tempo data"));
+	}
 
-		let mut did_user_intend = false;
-		if let Some(vertical_spacing_multiplier_override) =
-			extract_vertical_spacing_multiplier(arg, &mut did_user_intend)
-		{
-			vertical_spacing_multiplier = vertical_spacing_multiplier_override;
-			continue;
+	notes.sort_by_key(|&(pos, _, _)| pos);
+	let mut pattern = pattern_draw::Pattern { rows: Vec::new() };
+	for (pos, column, note_type) in notes {
+		let row_index = pos.max(0) as usize;
+		let end_row_index = match note_type {
+			pattern_draw::NoteType::Hold { length } | pattern_draw::NoteType::Roll { length } => {
+				row_index + length as usize - 1
+			}
+			_ => row_index,
+		};
+		if end_row_index >= MAX_ARROWVORTEX_ROWS {
+			return Err(too_long().into());
 		}
-		if did_user_intend {
-			poise::say_reply(ctx, format!("\"{}\" is not a valid zoom option", arg)).await?;
+		while pattern.rows.len() <= row_index {
+			pattern.rows.push(pattern_draw::Row { notes: Vec::new() });
 		}
+		pattern.rows[row_index]
+			.notes
+			.push((pattern_draw::Lane::Index(column as _), note_type));
+	}
 
-		let mut did_user_intend = false;
-		if let Some(scroll_direction_override) = extract_scroll_direction(arg, &mut did_user_intend)
-		{
-			scroll_direction = scroll_direction_override;
-			continue;
+	Ok((pattern, notice))
+}
+
+/// draw_pattern spaces the rows of the lowest snap one note apart. Measures of sparse charts are
+/// often written in 4ths though, so SM note data is scaled by this to space 16ths one note apart
+/// like in the regular pattern syntax
+pub(super) fn sm_notes_spacing_multiplier(
+	segments: &[(pattern_draw::Pattern, pattern_draw::FractionalSnap)],
+) -> f32 {
+	match segments.iter().map(|(_, snap)| snap.snap_number()).min() {
+		Some(lowest_snap) => 16.0 / lowest_snap as f32,
+		None => 1.0,
+	}
+}
+
+/// Encodes pattern segments as row-based ArrowVortex clipboard data
+fn encode_arrowvortex_pattern(
+	segments: &[(pattern_draw::Pattern, pattern_draw::FractionalSnap)],
+	keymode: usize,
+) -> Result<String, Error> {
+	let notes = pattern_draw::place_notes(segments)
+		.notes
+		.into_iter()
+		.map(|note| {
+			let column = note.lane.column_number_with_keymode(keymode as u32);
+			if column as usize >= keymode {
+				return Err(PatternError::InvalidLaneForKeymode {
+					human_readable_lane: column as usize + 1,
+					keymode,
+				});
+			}
+			let end_pos = note.end_row.unwrap_or(note.row) as i32;
+			let kind = match note.note_type {
+				pattern_draw::NoteType::Tap => arrowvortex_clipboard::NoteKind::Tap,
+				pattern_draw::NoteType::Mine => arrowvortex_clipboard::NoteKind::Mine,
+				pattern_draw::NoteType::Hold { .. } => {
+					arrowvortex_clipboard::NoteKind::Hold { end_pos }
+				}
+				pattern_draw::NoteType::Roll { .. } => {
+					arrowvortex_clipboard::NoteKind::Roll { end_pos }
+				}
+				pattern_draw::NoteType::Lift => arrowvortex_clipboard::NoteKind::Lift,
+				pattern_draw::NoteType::Fake => arrowvortex_clipboard::NoteKind::Fake,
+			};
+			Ok(arrowvortex_clipboard::Note {
+				pos: note.row as i32,
+				column: column as _,
+				kind,
+			})
+		})
+		.collect::<Result<Vec<_>, PatternError>>()?;
+
+	arrowvortex_clipboard::encode_row_based(&notes)
+		.map_err(|e| anyhow::anyhow!("Failed to encode ArrowVortex pattern: {}", e))
+}
+
+fn extract_snap(string: &str, user_intended: &mut bool) -> Option<pattern_draw::FractionalSnap> {
+	const ENDINGS: &[&str] = &["st", "sts", "nd", "nds", "rd", "rds", "th", "ths"];
+
+	let characters_to_truncate = ENDINGS
+		.iter()
+		.find(|&ending| string.ends_with(ending))?
+		.len();
+	// UNWRAP: we're only removing up to the string length, so we can't go out-of-bounds
+	let string_without_ending = string
+		.get(..(string.len() - characters_to_truncate))
+		.unwrap();
+	let snap: u32 = string_without_ending.parse().ok()?;
+	*user_intended = true;
+	pattern_draw::FractionalSnap::from_snap_number(snap)
+}
+
+fn extract_vertical_spacing_multiplier(string: &str, user_intended: &mut bool) -> Option<f32> {
+	if !string.ends_with('x') {
+		return None;
+	};
+	// UNWRAP: at this point the string must have 'x' at the end so we can safely strip one char
+	let vertical_spacing_multiplier: f32 =
+		string.get(..(string.len() - 1)).unwrap().parse().ok()?;
+	*user_intended = true;
+	if vertical_spacing_multiplier > 0.0 {
+		Some(vertical_spacing_multiplier)
+	} else {
+		None
+	}
+}
+
+fn extract_scroll_direction(
+	string: &str,
+	_user_intended: &mut bool,
+) -> Option<etterna::ScrollDirection> {
+	match string.to_lowercase().as_str() {
+		"up" | "upscroll" => Some(etterna::ScrollDirection::Upscroll),
+		"down" | "downscroll" | "reverse" => Some(etterna::ScrollDirection::Downscroll),
+		_ => None,
+	}
+}
+
+fn extract_keymode(string: &str, user_intended: &mut bool) -> Option<u32> {
+	if !(string.ends_with('k') || string.ends_with('K')) {
+		return None;
+	}
+
+	// UNWRAP: at this point the string must have 'k' at the end so we can safely strip one char
+	let keymode: u32 = string.get(..(string.len() - 1)).unwrap().parse().ok()?;
+	*user_intended = true;
+	if keymode > 0 {
+		Some(keymode)
+	} else {
+		None
+	}
+}
+
+fn extract_bpm(string: &str, user_intended: &mut bool) -> Option<f32> {
+	if !string.to_ascii_lowercase().ends_with("bpm") {
+		return None;
+	}
+
+	// UNWRAP: at this point the string must have "bpm" at the end so we can safely strip it
+	let bpm: f32 = string.get(..(string.len() - 3)).unwrap().parse().ok()?;
+	*user_intended = true;
+	if bpm > 0.0 && bpm <= 1000.0 {
+		Some(bpm)
+	} else {
+		None
+	}
+}
+
+/// Parses modifier keywords like `mirror`, `rotate:2`, `shuffle:42` or `convert:6k`
+fn extract_transform(string: &str, user_intended: &mut bool) -> Option<pattern_draw::Transform> {
+	let string = string.to_ascii_lowercase();
+	let (name, parameter) = match string.split_once(':') {
+		Some((name, parameter)) => (name, Some(parameter)),
+		None => (string.as_str(), None),
+	};
+
+	let transform = match (name, parameter) {
+		("mirror", None) => pattern_draw::Transform::Mirror,
+		("flip", None) => pattern_draw::Transform::Flip,
+		("nojacks", None) => pattern_draw::Transform::NoJacks,
+		("nojack", None) => pattern_draw::Transform::NoJacks,
+		("rotate", None) => pattern_draw::Transform::Rotate(1),
+		("shuffle", None) => pattern_draw::Transform::Shuffle {
+			seed: rand::random(),
+		},
+		("rotate", Some(offset)) => {
+			*user_intended = true;
+			pattern_draw::Transform::Rotate(offset.parse().ok()?)
 		}
-		if did_user_intend {
-			poise::say_reply(ctx, format!("\"{}\" is not a valid scroll direction", arg)).await?;
+		("shuffle", Some(seed)) => {
+			*user_intended = true;
+			pattern_draw::Transform::Shuffle {
+				seed: seed.parse().ok()?,
+			}
+		}
+		("convert", Some(keymode)) => {
+			*user_intended = true;
+			let keymode: usize = keymode.trim_end_matches('k').parse().ok()?;
+			if keymode == 0 {
+				return None;
+			}
+			pattern_draw::Transform::ConvertKeymode { keymode }
 		}
+		_ => return None,
+	};
+	Some(transform)
+}
 
-		let mut did_user_intend = false;
-		if let Some(keymode) = extract_keymode(arg, &mut did_user_intend) {
-			keymode_override = Some(keymode);
-			continue;
+/// Character position of `arg` within `input`. `arg` must be a substring of `input`
+fn char_position(input: &str, arg: &str) -> usize {
+	let byte_offset = arg.as_ptr() as usize - input.as_ptr() as usize;
+	input[..byte_offset].chars().count()
+}
+
+/// Lists the warnings, each with an excerpt of the input and a caret pointing at the problem
+fn format_parse_warnings(input: &str, warnings: &[(pattern_draw::ParseWarning, usize)]) -> String {
+	const MAX_WARNINGS: usize = 5;
+	// Number of characters shown on each side of the problem
+	const CONTEXT_LENGTH: usize = 20;
+
+	// Newlines and backticks would break the code block
+	let input = input
+		.chars()
+		.map(|c| match c {
+			'\n' | '\r' | '\t' => ' ',
+			'`' => '\'',
+			c => c,
+		})
+		.collect::<Vec<_>>();
+
+	let mut message = String::new();
+	for (warning, position) in warnings.iter().take(MAX_WARNINGS) {
+		let start = position.saturating_sub(CONTEXT_LENGTH);
+		let end = (position + CONTEXT_LENGTH + 1).min(input.len());
+		let mut excerpt = input[start..end].iter().collect::<String>();
+		let mut caret_offset = position - start;
+		if start > 0 {
+			excerpt.insert(0, '…');
+			caret_offset += 1;
 		}
-		if did_user_intend {
-			poise::say_reply(ctx, format!("\"{}\" is not a valid keymode", arg)).await?;
+		if end < input.len() {
+			excerpt.push('…');
 		}
 
-		// if nothing matched, this is just an ordinary part of the pattern
-		pattern_buffer += arg;
+		message += &format!(
+			"Column {}: {}\n```\n{}\n{}^\n```\n",
+			position + 1,
+			warning,
+			excerpt,
+			" ".repeat(caret_offset),
+		);
 	}
-	if pattern_buffer.len() > 0 {
-		segments.push((pattern_buffer, snap));
+	if warnings.len() > MAX_WARNINGS {
+		message += &format!("...and {} more", warnings.len() - MAX_WARNINGS);
 	}
+	message
+}
 
-	let segments = if pattern.contains("ArrowVortex:notes:") {
-		let mut full_pattern = String::new();
-		for (partial_pattern, _) in &segments {
-			full_pattern += partial_pattern;
-		}
-		// Unwrap code block ticks
-		if let Some(s) = full_pattern
-			.strip_prefix("```")
-			.and_then(|s| s.strip_suffix("```"))
-		{
-			full_pattern = s.trim().to_owned();
-		}
+/// Human-readable summary of the statistics of a pattern
+fn format_pattern_analysis(analysis: &pattern_draw::PatternAnalysis) -> String {
+	const CHORD_NAMES: &[&str] = &["single notes", "jumps", "hands", "quads"];
 
-		let notes = match arrowvortex_clipboard::decode(full_pattern.as_bytes())
-			.map_err(|e| anyhow::anyhow!("Failed to decode ArrowVortex pattern: {}", e))?
-		{
-			arrowvortex_clipboard::DecodeResult::RowBasedNotes(notes) => notes,
-			arrowvortex_clipboard::DecodeResult::TimeBasedNotes(_) => {
-				return Err(anyhow::anyhow!(
-					"Please change to row-based mode before copying the notes"
-				))
+	let chords = analysis
+		.chord_size_counts
+		.iter()
+		.enumerate()
+		.filter(|&(_, &count)| count > 0)
+		.map(|(i, count)| match CHORD_NAMES.get(i) {
+			Some(chord_name) => format!("{} {}", count, chord_name),
+			None => format!("{} {}-note chords", count, i + 1),
+		})
+		.collect::<Vec<_>>()
+		.join(", ");
+
+	let longest_jack = match analysis.longest_jack {
+		Some((length, lane)) => format!(" (longest: {} notes in lane {})", length, lane + 1),
+		None => String::new(),
+	};
+
+	let lanes = analysis
+		.lane_note_counts
+		.iter()
+		.zip(&analysis.longest_anchors)
+		.enumerate()
+		.map(|(lane, (note_count, longest_anchor))| {
+			format!(
+				"Lane {}: {} notes, longest anchor {}",
+				lane + 1,
+				note_count,
+				longest_anchor
+			)
+		})
+		.collect::<Vec<_>>()
+		.join("\n");
+
+	format!(
+		"**Notes:** {} in {} rows ({:.2} per row)\n\
+		**Chords:** {}\n\
+		**Jacks:** {}{}\n\
+		**Trills:** {} rows\n\
+		**Rolls:** {} rows\n\
+		{}",
+		analysis.num_notes,
+		analysis.num_rows,
+		analysis.chord_density(),
+		chords,
+		analysis.jack_count,
+		longest_jack,
+		analysis.trill_rows,
+		analysis.roll_rows,
+		lanes,
+	)
+}
+
+/// A pattern string of a single snap, as written in the command input
+struct UnparsedSegment {
+	/// Pattern string with whitespace and options removed
+	text: String,
+	snap: pattern_draw::FractionalSnap,
+	/// For each character of `text`, its position in the command input
+	source_positions: Vec<usize>,
+}
+
+/// A `|`-separated part of the pattern command input, before parsing the pattern
+struct PatternPart<'a> {
+	text: &'a str,
+	segments: Vec<UnparsedSegment>,
+	transforms: Vec<pattern_draw::Transform>,
+	caption: String,
+}
+
+/// A parsed pattern, ready to be drawn
+struct PatternColumn {
+	segments: Vec<(pattern_draw::Pattern, pattern_draw::FractionalSnap)>,
+	keymode: u32,
+	vertical_spacing_multiplier: f32,
+	caption: String,
+}
+
+/// Options and patterns written in the input of a pattern command
+struct PatternInput<'a> {
+	columns: Vec<PatternColumn>,
+	noteskin_override: Option<&'a Noteskin>,
+	scroll_direction: etterna::ScrollDirection,
+	/// The zoom option, before it's adjusted to the snap of imported note data
+	scroll_speed: f32,
+	bpm: f32,
+	export: bool,
+	analyze: bool,
+	animate: bool,
+	svg: bool,
+	overlays: pattern_draw::Overlays,
+	note_coloring: pattern_draw::NoteColoring,
+}
+
+/// Parses the options and patterns of a pattern command. Invalid options and typos in the patterns
+/// are reported to the user, but don't stop the command
+async fn parse_pattern_input<'a>(
+	ctx: Context<'a>,
+	pattern: &str,
+) -> Result<PatternInput<'a>, Error> {
+	let mut noteskin_override = None;
+	let mut keymode_override = None;
+	let preferences = ctx.data().lock_data().pattern_preferences(ctx.author().id);
+	let default_snap = preferences
+		.snap
+		.and_then(pattern_draw::FractionalSnap::from_snap_number)
+		.unwrap_or_else(|| etterna::Snap::_16th.into());
+	let mut vertical_spacing_multiplier = preferences.zoom.unwrap_or(1.0);
+	let mut scroll_direction = ctx
+		.data()
+		.lock_data()
+		.scroll(ctx.author().id)
+		.unwrap_or(etterna::ScrollDirection::Upscroll);
+	let mut export = false;
+	let mut analyze = false;
+	let mut animate = false;
+	let mut svg = false;
+	let mut overlays = pattern_draw::Overlays::default();
+	let mut note_coloring = pattern_draw::NoteColoring::default();
+	let mut bpm = 150.0;
+	// Patterns separated by `|` are drawn next to each other
+	let mut parts = Vec::new();
+
+	let extract_noteskin =
+		|string: &str, _user_intended: &mut bool| ctx.data().noteskin_provider.get(string);
+
+	// The ArrowVortex clipboard encoding may itself contain `|`
+	let part_texts = if pattern.contains("ArrowVortex:notes:") {
+		vec![pattern]
+	} else {
+		pattern.split('|').collect()
+	};
+	for part in part_texts {
+		let mut snap = default_snap;
+		let mut segments = Vec::new();
+		let mut transforms = Vec::new();
+		// Everything except the options that apply to all patterns
+		let mut caption = Vec::new();
+
+		let mut pattern_buffer = String::new();
+		let mut source_positions = Vec::new();
+		for arg in part.split_whitespace() {
+			let mut did_user_intend = false;
+			if let Some(new_snap) = extract_snap(arg, &mut did_user_intend) {
+				if pattern_buffer.len() > 0 {
+					segments.push(UnparsedSegment {
+						text: std::mem::take(&mut pattern_buffer),
+						snap,
+						source_positions: std::mem::take(&mut source_positions),
+					});
+				}
+				snap = new_snap;
+				caption.push(arg);
+				continue;
 			}
-			arrowvortex_clipboard::DecodeResult::TempoEvents(_) => {
-				return Err(anyhow::anyhow!("Please paste note data, not tempo data"))
+			if did_user_intend {
+				poise::say_reply(ctx, format!("\"{}\" is not a valid snap", arg)).await?;
 			}
-		};
 
-		let mut pattern = pattern_draw::Pattern { rows: Vec::new() };
-		let mut row = Vec::new();
-		let mut prev_pos = 0;
-		for note in notes {
-			if note.pos != prev_pos {
-				pattern.rows.push(pattern_draw::Row { notes: row });
-				row = Vec::new();
+			let mut did_user_intend = false;
+			if let Some(noteskin) = extract_noteskin(arg, &mut did_user_intend) {
+				noteskin_override = Some(noteskin);
+				continue;
+			}
+			if did_user_intend {
+				poise::say_reply(ctx, format!("\"{}\" is not a valid noteskin name", arg)).await?;
+			}
 
-				for _ in (prev_pos + 1)..note.pos {
-					pattern.rows.push(pattern_draw::Row { notes: Vec::new() });
-				}
-				prev_pos = note.pos;
+			let mut did_user_intend = false;
+			if let Some(vertical_spacing_multiplier_override) =
+				extract_vertical_spacing_multiplier(arg, &mut did_user_intend)
+			{
+				vertical_spacing_multiplier = vertical_spacing_multiplier_override;
+				continue;
+			}
+			if did_user_intend {
+				poise::say_reply(ctx, format!("\"{}\" is not a valid zoom option", arg)).await?;
 			}
 
-			let note_type = match note.kind {
-				arrowvortex_clipboard::NoteKind::Tap => pattern_draw::NoteType::Tap,
-				arrowvortex_clipboard::NoteKind::Mine => pattern_draw::NoteType::Mine,
-				_ => continue, // ignore unsupported note types
-			};
-			row.push((pattern_draw::Lane::Index(note.column as _), note_type));
+			let mut did_user_intend = false;
+			if let Some(scroll_direction_override) =
+				extract_scroll_direction(arg, &mut did_user_intend)
+			{
+				scroll_direction = scroll_direction_override;
+				continue;
+			}
+			if did_user_intend {
+				poise::say_reply(ctx, format!("\"{}\" is not a valid scroll direction", arg))
+					.await?;
+			}
+
+			let mut did_user_intend = false;
+			if let Some(keymode) = extract_keymode(arg, &mut did_user_intend) {
+				keymode_override = Some(keymode);
+				continue;
+			}
+			if did_user_intend {
+				poise::say_reply(ctx, format!("\"{}\" is not a valid keymode", arg)).await?;
+			}
+
+			let mut did_user_intend = false;
+			if let Some(new_bpm) = extract_bpm(arg, &mut did_user_intend) {
+				bpm = new_bpm;
+				continue;
+			}
+			if did_user_intend {
+				poise::say_reply(ctx, format!("\"{}\" is not a valid BPM", arg)).await?;
+			}
+
+			let mut did_user_intend = false;
+			if let Some(transform) = extract_transform(arg, &mut did_user_intend) {
+				transforms.push(transform);
+				caption.push(arg);
+				continue;
+			}
+			if did_user_intend {
+				poise::say_reply(ctx, format!("\"{}\" is not a valid modifier", arg)).await?;
+			}
+
+			if arg.eq_ignore_ascii_case("animate") {
+				animate = true;
+				continue;
+			}
+			if arg.eq_ignore_ascii_case("analyze") || arg.eq_ignore_ascii_case("analyse") {
+				analyze = true;
+				continue;
+			}
+			if arg.eq_ignore_ascii_case("export") {
+				export = true;
+				continue;
+			}
+			if arg.eq_ignore_ascii_case("svg") {
+				svg = true;
+				continue;
+			}
+			if arg.eq_ignore_ascii_case("rhythm") {
+				note_coloring.rhythm = true;
+				continue;
+			}
+			if arg.eq_ignore_ascii_case("tint") {
+				note_coloring.tint = true;
+				continue;
+			}
+			if arg.eq_ignore_ascii_case("grid") {
+				overlays.beat_lines = true;
+				overlays.beat_numbers = true;
+				overlays.snap_legend = true;
+				continue;
+			}
+			if arg.eq_ignore_ascii_case("hands") {
+				overlays.hands = true;
+				continue;
+			}
+
+			// if nothing matched, this is just an ordinary part of the pattern
+			pattern_buffer += arg;
+			let arg_position = char_position(pattern, arg);
+			source_positions.extend(arg_position..(arg_position + arg.chars().count()));
+			caption.push(arg);
 		}
-		if !row.is_empty() {
-			pattern.rows.push(pattern_draw::Row { notes: row });
+		if pattern_buffer.len() > 0 {
+			segments.push(UnparsedSegment {
+				text: pattern_buffer,
+				snap,
+				source_positions,
+			});
 		}
+		parts.push(PatternPart {
+			text: part,
+			segments,
+			transforms,
+			caption: caption.join(" "),
+		});
+	}
 
-		vertical_spacing_multiplier /= 6.0;
-		vec![(pattern, etterna::Snap::_192th.into())]
-	} else {
-		segments
-			.into_iter()
-			.map(|(partial_pattern, snap)| (pattern_draw::parse_pattern(&partial_pattern), snap))
-			.collect()
+	// The vertical spacing is adjusted below for imported note data, but the scroll speed of
+	// animations doesn't depend on the snap
+	let scroll_speed = vertical_spacing_multiplier;
+
+	// Shared by all patterns, so that macros can be used in the patterns after their definition
+	let mut pattern_parser = pattern_draw::PatternParser::new(MAX_SPRITES, keymode_override);
+	// Along with their position in the command input
+	let mut parse_warnings = Vec::new();
+	let mut columns = Vec::new();
+	for part in parts {
+		// Parts with only options, like the `down` in `down | 1234`, aren't patterns on their own
+		if part.segments.is_empty() {
+			continue;
+		}
+
+		let mut vertical_spacing_multiplier = vertical_spacing_multiplier;
+		let mut segments = if part.text.contains("ArrowVortex:notes:") {
+			let mut full_pattern = String::new();
+			for segment in &part.segments {
+				full_pattern += &segment.text;
+			}
+
+			let (pattern, notice) = parse_arrowvortex_pattern(&full_pattern)?;
+			if let Some(notice) = notice {
+				poise::say_reply(ctx, notice).await?;
+			}
+
+			vertical_spacing_multiplier /= 6.0;
+			vec![(pattern, etterna::Snap::_192th.into())]
+		} else if pattern_draw::looks_like_sm_notes(part.text) {
+			let segments = pattern_draw::parse_sm_notes(part.text)?;
+			vertical_spacing_multiplier *= sm_notes_spacing_multiplier(&segments);
+			segments
+		} else {
+			let mut segments = Vec::new();
+			for segment in part.segments {
+				let (pattern, warnings) = pattern_parser.parse_with_warnings(&segment.text)?;
+				parse_warnings.extend(warnings.into_iter().map(|warning| {
+					let position = segment.source_positions[warning.position()];
+					(warning, position)
+				}));
+				segments.push((pattern, segment.snap));
+			}
+			segments
+		};
+
+		let mut keymode = if let Some(keymode) = keymode_override {
+			keymode
+		} else {
+			let highest_lane = segments
+				.iter()
+				.flat_map(|(pattern, _)| &pattern.rows)
+				// if the user entered `+pattern ldr`, was the highest column 3, or 4? remember, the
+				// meaning of `r` depends on keymode, but we don't know the keymode yet. I've
+				// decided to assume 4k in the fallback case
+				.filter_map(|row| {
+					row.notes
+						.iter()
+						.map(|(lane, _note_type)| lane.column_number_with_keymode(4))
+						.max()
+				})
+				.max()
+				.ok_or(PatternError::EmptyPattern)?;
+			let keymode = (highest_lane + 1) as u32;
+			keymode.max(4) // clamp keymode to a minimum of 4k. yes, 3k exists, but it's so niche that even if only three lanes are populated, the pattern is probably meant to be 4k
+		};
+
+		// Modifiers are applied in the order they were written
+		for &transform in &part.transforms {
+			pattern_draw::transform_pattern(&mut segments, transform, keymode as usize)?;
+			keymode = transform.resulting_keymode(keymode as usize) as u32;
+		}
+
+		columns.push(PatternColumn {
+			segments,
+			keymode,
+			vertical_spacing_multiplier,
+			caption: part.caption,
+		});
+	}
+
+	if !parse_warnings.is_empty() {
+		poise::say_reply(ctx, format_parse_warnings(pattern, &parse_warnings)).await?;
+	}
+
+	Ok(PatternInput {
+		columns,
+		noteskin_override,
+		scroll_direction,
+		scroll_speed,
+		bpm,
+		export,
+		analyze,
+		animate,
+		svg,
+		overlays,
+		note_coloring,
+	})
+}
+
+/// Visualize note patterns
+#[poise::command(prefix_command, slash_command, track_edits, check = "always_true")]
+pub async fn pattern(
+	ctx: Context<'_>,
+	#[rest]
+	#[description = "Pattern string to render"]
+	pattern: String,
+) -> Result<(), Error> {
+	// People are supposed to write `+help pattern` but some write `+pattern help` so let's help
+	// them as well :)
+	if pattern.eq_ignore_ascii_case("help") {
+		super::help::send_help(ctx, true).await?;
+		return Ok(());
+	}
+
+	let PatternInput {
+		columns,
+		noteskin_override,
+		scroll_direction,
+		scroll_speed,
+		bpm: animation_bpm,
+		export,
+		analyze,
+		animate,
+		svg,
+		overlays,
+		note_coloring,
+	} = parse_pattern_input(ctx, &pattern).await?;
+
+	let single_column = match &*columns {
+		[] => return Err(PatternError::EmptyPattern.into()),
+		[column] => Some(column),
+		_ => None,
 	};
+	if single_column.is_none() && (animate || export || svg) {
+		return Err(anyhow::anyhow!(
+			"`animate`, `export` and `svg` only work with a single pattern"
+		));
+	}
 
-	let keymode = if let Some(keymode) = keymode_override {
-		keymode
-	} else {
-		let highest_lane = segments
-			.iter()
-			.flat_map(|(pattern, _)| &pattern.rows)
-			// if the user entered `+pattern ldr`, was the highest column 3, or 4? remember, the
-			// meaning of `r` depends on keymode, but we don't know the keymode yet. I've
-			// decided to assume 4k in the fallback case
-			.filter_map(|row| {
-				row.notes
-					.iter()
-					.map(|(lane, _note_type)| lane.column_number_with_keymode(4))
-					.max()
-			})
-			.max()
-			.ok_or(PatternError::EmptyPattern)?;
-		let keymode = (highest_lane + 1) as u32;
-		keymode.max(4) // clamp keymode to a minimum of 4k. yes, 3k exists, but it's so niche that even if only three lanes are populated, the pattern is probably meant to be 4k
+	// All patterns share a noteskin, so pick one that supports the biggest keymode
+	// UNWRAP: we checked above that there's at least one column
+	let keymode = columns.iter().map(|column| column.keymode).max().unwrap();
+	let preferences = ctx.data().lock_data().pattern_preferences(ctx.author().id);
+	let preferred_noteskin = preferences
+		.noteskin(keymode)
+		.and_then(|name| ctx.data().noteskin_provider.get(name));
+	let noteskin = match noteskin_override.or(preferred_noteskin) {
+		Some(noteskin) => noteskin,
+		None => ctx
+			.data()
+			.noteskin_provider
+			.default_for_keymode(keymode)
+			.ok_or_else(|| anyhow::anyhow!("No noteskins are installed"))?,
 	};
 
-	let noteskin = if let Some(noteskin) = noteskin_override {
-		&noteskin
+	let (img_bytes, filename) = if let (true, Some(column)) = (animate, single_column) {
+		let img_bytes = pattern_draw::animate_pattern(pattern_draw::AnimationRecipe {
+			noteskin,
+			scroll_direction,
+			keymode: column.keymode as usize,
+			vertical_spacing_multiplier: scroll_speed,
+			bpm: animation_bpm,
+			frames_per_second: 30,
+			screen_height: noteskin.sprite_resolution() * 8,
+			pattern: &column.segments,
+			max_image_dimensions: (5000, 10000),
+			max_sprites: MAX_SPRITES,
+			max_total_sprites: 100_000,
+			max_total_pixels: 150_000_000,
+			max_strip_pixels: 20_000_000,
+			max_file_size: DISCORD_ATTACHMENT_SIZE_LIMIT,
+			note_coloring,
+		})?;
+
+		(img_bytes, "animation.png")
+	} else if let (true, Some(column)) = (svg, single_column) {
+		let svg_string = pattern_draw::draw_pattern_svg(pattern_draw::SvgPatternRecipe {
+			noteskin,
+			scroll_direction,
+			keymode: column.keymode as usize,
+			vertical_spacing_multiplier: column.vertical_spacing_multiplier,
+			pattern: &column.segments,
+			max_sprites: MAX_SPRITES,
+			note_coloring,
+		})?;
+
+		(svg_string.into_bytes(), "pattern.svg")
 	} else {
-		// choose a default noteskin
-		match keymode {
-			3 | 4 | 6 | 8 => &ctx.data().noteskin_provider.dbz,
-			5 | 10 => &ctx.data().noteskin_provider.delta_note,
-			7 | 9 => &ctx.data().noteskin_provider.sbz,
-			_ => &ctx.data().noteskin_provider.sbz, // fallback
-		}
-	};
+		let generated_pattern = if let Some(column) = single_column {
+			// Long patterns are wrapped into several columns instead of erroring
+			pattern_draw::draw_pattern_wrapped(pattern_draw::PatternRecipe {
+				noteskin,
+				scroll_direction,
+				keymode: column.keymode as usize, /* I thought I had changedit to u32 in pattern_draw???? */
+				vertical_spacing_multiplier: column.vertical_spacing_multiplier,
+				pattern: &column.segments,
+				max_image_dimensions: (5000, 10000),
+				max_sprites: MAX_SPRITES,
+				overlays,
+				note_coloring,
+			})?
+		} else {
+			let compared_patterns = columns
+				.iter()
+				.map(|column| pattern_draw::ComparedPattern {
+					keymode: column.keymode as usize,
+					vertical_spacing_multiplier: column.vertical_spacing_multiplier,
+					pattern: &column.segments,
+					caption: &column.caption,
+				})
+				.collect::<Vec<_>>();
+			pattern_draw::draw_pattern_comparison(pattern_draw::ComparisonRecipe {
+				noteskin,
+				scroll_direction,
+				patterns: &compared_patterns,
+				gap: noteskin.sprite_resolution(),
+				max_image_dimensions: (5000, 10000),
+				max_sprites: MAX_SPRITES,
+				overlays,
+				note_coloring,
+			})?
+		};
 
-	let generated_pattern = pattern_draw::draw_pattern(pattern_draw::PatternRecipe {
-		noteskin,
-		scroll_direction,
-		keymode: keymode as usize, /* I thought I had changedit to u32 in pattern_draw???? */
-		vertical_spacing_multiplier,
-		pattern: &segments,
-		max_image_dimensions: (5000, 10000),
-		max_sprites: 1000,
-	})?;
-
-	let mut img_bytes = Vec::with_capacity(1_000_000); // preallocate 1 MB for the img
-	image::DynamicImage::ImageRgba8(generated_pattern)
-		.write_to(
-			&mut std::io::Cursor::new(&mut img_bytes),
-			image::ImageOutputFormat::Png,
-		)
-		.map_err(pattern_draw::Error::ImageError)?;
+		let mut img_bytes = Vec::with_capacity(1_000_000); // preallocate 1 MB for the img
+		image::DynamicImage::ImageRgba8(generated_pattern)
+			.write_to(
+				&mut std::io::Cursor::new(&mut img_bytes),
+				image::ImageOutputFormat::Png,
+			)
+			.map_err(pattern_draw::Error::ImageError)?;
+
+		(img_bytes, "output.png")
+	};
 
 	let image_attachment = serenity::AttachmentType::Bytes {
 		data: img_bytes.into(),
-		filename: "output.png".to_owned(),
+		filename: filename.to_owned(),
+	};
+	let analyses = if analyze {
+		columns
+			.iter()
+			.map(|column| {
+				let analysis =
+					pattern_draw::analyze_pattern(&column.segments, column.keymode as usize)?;
+				Ok((analysis, column.caption.as_str()))
+			})
+			.collect::<Result<Vec<_>, PatternError>>()?
+	} else {
+		Vec::new()
 	};
-	match ctx {
-		poise::Context::Prefix(_) => {
-			poise::send_reply(ctx, |f| f.attachment(image_attachment)).await?;
+	let export = match (export, single_column) {
+		(true, Some(column)) => Some((
+			pattern_draw::pattern_to_sm(&column.segments, column.keymode as usize)?,
+			encode_arrowvortex_pattern(&column.segments, column.keymode as usize)?,
+		)),
+		_ => None,
+	};
+
+	if let poise::Context::Application(_) = ctx {
+		// We need to send some initial response! Only follow up messages support
+		// attachments
+		poise::say_reply(ctx, format!("Pattern `{}`", pattern)).await?;
+	}
+	poise::send_reply(ctx, |f| {
+		f.attachment(image_attachment);
+		for (analysis, caption) in &analyses {
+			let title = if analyses.len() > 1 {
+				format!("{}: {}", caption, analysis.classification)
+			} else {
+				format!("Pattern analysis: {}", analysis.classification)
+			};
+			f.embed(|e| {
+				e.color(crate::ETTERNA_COLOR)
+					.title(title)
+					.description(format_pattern_analysis(analysis))
+			});
 		}
-		poise::Context::Application(_) => {
-			// We need to send some initial response! Only follow up messages support
-			// attachments
-			poise::say_reply(ctx, format!("Pattern `{}`", pattern)).await?;
-			poise::send_reply(ctx, |f| f.attachment(image_attachment)).await?;
+		if let Some((sm_notes, arrowvortex_clipboard)) = export {
+			f.attachment(serenity::AttachmentType::Bytes {
+				data: sm_notes.into_bytes().into(),
+				filename: "pattern.sm".to_owned(),
+			});
+			// Long clipboard strings don't fit into a message
+			if arrowvortex_clipboard.len() > 1900 {
+				f.attachment(serenity::AttachmentType::Bytes {
+					data: arrowvortex_clipboard.into_bytes().into(),
+					filename: "arrowvortex.txt".to_owned(),
+				});
+			} else {
+				f.content(format!("```\n{}\n```", arrowvortex_clipboard));
+			}
 		}
-	}
+		f
+	})
+	.await?;
+
+	Ok(())
+}
+
+/// Graph the notes per second of a pattern
+///
+/// Takes the same input as `+pattern`, including .sm note data. The BPM is 150 unless written
+/// like `190bpm`
+#[poise::command(prefix_command, slash_command, track_edits)]
+pub async fn density(
+	ctx: Context<'_>,
+	#[rest]
+	#[description = "Pattern string or .sm note data, optionally with a BPM like `190bpm`"]
+	pattern: String,
+) -> Result<(), Error> {
+	let input = parse_pattern_input(ctx, &pattern).await?;
+	let column = match &*input.columns {
+		[] => return Err(PatternError::EmptyPattern.into()),
+		[column] => column,
+		_ => {
+			return Err(anyhow::anyhow!(
+				"`+density` only works with a single pattern"
+			))
+		}
+	};
+
+	let density =
+		pattern_draw::pattern_density(&column.segments, column.keymode as usize, input.bpm)?;
+	let graph = super::skill_graph::render::draw_density_graph(&density)?;
+
+	let format_duration = |seconds: f32| {
+		if seconds < 60.0 {
+			format!("{:.1}s", seconds)
+		} else {
+			format!("{}:{:02}", seconds as u32 / 60, seconds as u32 % 60)
+		}
+	};
+	poise::send_reply(ctx, |f| {
+		f.content(format!(
+			"**Length:** {} at {} BPM\n**Average:** {:.1} NPS\n**Peak:** {:.1} NPS (counted over {:.2}s)",
+			format_duration(density.duration),
+			input.bpm,
+			density.average_nps,
+			density.peak_nps(),
+			density.window,
+		))
+		.attachment(serenity::AttachmentType::Bytes {
+			data: graph.into(),
+			filename: "density.png".to_owned(),
+		})
+	})
+	.await?;
 
 	Ok(())
 }
@@ -384,14 +1042,138 @@ pub async fn scrollset(
 	ctx: Context<'_>,
 	#[description = "Scroll direction"] scroll: String,
 ) -> Result<(), Error> {
-	let scroll = match scroll.to_lowercase().as_str() {
-		"down" | "downscroll" | "reverse" => etterna::ScrollDirection::Downscroll,
-		"up" | "upscroll" => etterna::ScrollDirection::Upscroll,
-		_ => return Err(anyhow::anyhow!("No such scroll '{}'", scroll)),
-	};
+	let scroll = extract_scroll_direction(&scroll, &mut false)
+		.ok_or_else(|| anyhow::anyhow!("No such scroll '{}'", scroll))?;
 
 	ctx.data().lock_data().set_scroll(ctx.author().id, scroll);
 	poise::say_reply(ctx, format!("Your scroll type is now {:?}", scroll)).await?;
 
 	Ok(())
 }
+
+/// Set your defaults for the pattern command
+///
+/// Call this command with the same options as `+pattern`, for example `+patternset lambda 2x down`.
+/// A keymode followed by a noteskin, like `6k dbz`, sets the noteskin for that keymode only.
+/// Call it without options to see your current settings, or with `reset` to remove them, including
+/// the scroll direction from `+scrollset`
+#[poise::command(prefix_command, aliases("setpattern"), track_edits, slash_command)]
+pub async fn patternset(
+	ctx: Context<'_>,
+	#[rest]
+	#[description = "Options like noteskin, snap, zoom or scroll"]
+	options: Option<String>,
+) -> Result<(), Error> {
+	let options = options.unwrap_or_default();
+	let mut preferences = ctx.data().lock_data().pattern_preferences(ctx.author().id);
+	let mut scroll = None;
+
+	let reset = options
+		.split_whitespace()
+		.any(|arg| arg.eq_ignore_ascii_case("reset"));
+	if reset {
+		preferences = Default::default();
+	}
+
+	let mut keymode = None;
+	for arg in options.split_whitespace() {
+		if arg.eq_ignore_ascii_case("reset") {
+			continue;
+		}
+
+		let mut did_user_intend = false;
+		if let Some(snap) = extract_snap(arg, &mut did_user_intend) {
+			preferences.snap = Some(snap.snap_number());
+			continue;
+		}
+		if did_user_intend {
+			return Err(anyhow::anyhow!("\"{}\" is not a valid snap", arg));
+		}
+
+		if let Some(noteskin) = ctx.data().noteskin_provider.canonical_name(arg) {
+			match keymode.take() {
+				Some(keymode) => {
+					preferences
+						.keymode_noteskins
+						.insert(keymode, noteskin.to_owned());
+				}
+				None => preferences.noteskin = Some(noteskin.to_owned()),
+			}
+			continue;
+		}
+
+		let mut did_user_intend = false;
+		if let Some(zoom) = extract_vertical_spacing_multiplier(arg, &mut did_user_intend) {
+			preferences.zoom = Some(zoom);
+			continue;
+		}
+		if did_user_intend {
+			return Err(anyhow::anyhow!("\"{}\" is not a valid zoom option", arg));
+		}
+
+		let mut did_user_intend = false;
+		if let Some(scroll_direction) = extract_scroll_direction(arg, &mut did_user_intend) {
+			scroll = Some(scroll_direction);
+			continue;
+		}
+
+		let mut did_user_intend = false;
+		if let Some(new_keymode) = extract_keymode(arg, &mut did_user_intend) {
+			keymode = Some(new_keymode);
+			continue;
+		}
+
+		return Err(anyhow::anyhow!(
+			"Unknown option \"{}\". Valid options are noteskins, snaps, zoom and scroll direction",
+			arg
+		));
+	}
+	if let Some(keymode) = keymode {
+		return Err(anyhow::anyhow!(
+			"Please write a noteskin after \"{}k\" to choose the noteskin for {}k",
+			keymode,
+			keymode
+		));
+	}
+
+	let scroll = {
+		let mut data = ctx.data().lock_data();
+		if reset {
+			data.remove_scroll(ctx.author().id);
+		}
+		if let Some(scroll) = scroll {
+			data.set_scroll(ctx.author().id, scroll);
+		}
+		if !options.trim().is_empty() {
+			data.set_pattern_preferences(ctx.author().id, preferences.clone());
+		}
+		data.scroll(ctx.author().id)
+	};
+
+	let mut keymode_noteskins = preferences.keymode_noteskins.iter().collect::<Vec<_>>();
+	keymode_noteskins.sort();
+	let mut noteskin = preferences
+		.noteskin
+		.unwrap_or_else(|| "depends on keymode".to_owned());
+	for (keymode, keymode_noteskin) in keymode_noteskins {
+		noteskin += &format!(", {} in {}k", keymode_noteskin, keymode);
+	}
+
+	poise::say_reply(
+		ctx,
+		format!(
+			"Your pattern settings:\n\
+			- Noteskin: {}\n\
+			- Snap: {}ths\n\
+			- Zoom: {}x\n\
+			- Scroll: {:?}",
+			noteskin,
+			preferences.snap.unwrap_or(16),
+			preferences.zoom.unwrap_or(1.0),
+			scroll.unwrap_or(etterna::ScrollDirection::Upscroll),
+		),
+	)
+	.await?;
+
+	Ok(())
+}
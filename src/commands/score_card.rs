@@ -1,21 +1,32 @@
 //! All commands that spawn a score card
 
-use crate::{Context, Error};
+use crate::{serenity, Context, Error};
 
 #[derive(Debug)]
 pub struct InvalidJudge;
 impl std::fmt::Display for InvalidJudge {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		f.write_str("Please write the judge like `J5` or `J8`")
+		f.write_str(
+			"Please write the judge like `J5`, `J4.5` or `15ms`, or `all` to compare every judge",
+		)
 	}
 }
 impl std::error::Error for InvalidJudge {}
 
-pub struct Judge(pub &'static etterna::Judge);
+pub struct Judge(pub crate::JudgeSelection);
 impl std::str::FromStr for Judge {
 	type Err = InvalidJudge;
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.eq_ignore_ascii_case("all") {
+			return Ok(Self(crate::JudgeSelection::All));
+		}
+		// As a command argument, timing windows don't need a `judge` or `windows` in front
+		let windows = || {
+			let windows_ms = s.strip_suffix("ms")?.trim_end();
+			crate::parse_judge_windows(windows_ms).map(crate::JudgeSelection::Single)
+		};
 		crate::extract_judge_from_string(s)
+			.or_else(windows)
 			.map(Self)
 			.ok_or(InvalidJudge)
 	}
@@ -37,7 +48,6 @@ pub async fn rs(
 		Some(x) => x,
 		None => ctx.data().get_eo_username(ctx.author()).await?,
 	};
-	let alternative_judge = alternative_judge.map(|j| j.0);
 
 	let user_id = ctx.data().get_eo_user_id(&eo_username).await?;
 
@@ -70,7 +80,7 @@ pub async fn rs(
 			user_id: Some(user_id),
 			username: Some(&eo_username),
 			show_ssrs_and_judgements_and_modifiers: true,
-			alternative_judge,
+			judges: alternative_judge.as_ref().map(|j| &j.0),
 			draw_mean_instead_of_wifescore: false,
 		},
 	)
@@ -178,7 +188,7 @@ pub async fn randomscore(
 			user_id: Some(user_eo_id),
 			username: Some(&username),
 			show_ssrs_and_judgements_and_modifiers: true,
-			alternative_judge: judge.map(|x| x.0),
+			judges: judge.as_ref().map(|x| &x.0),
 			draw_mean_instead_of_wifescore: false,
 		},
 	)
@@ -186,3 +196,20 @@ pub async fn randomscore(
 
 	Ok(())
 }
+
+/// Analyze an Etterna replay file, for scores that aren't on EO
+///
+/// Attach a file from Etterna's `Save/ReplaysV2` folder. Replay files don't contain the BPM of the
+/// chart, so pass it to get correct note times and tap speeds: `+replay 180 J7`
+#[poise::command(prefix_command, slash_command, track_edits)]
+pub async fn replay(
+	ctx: Context<'_>,
+	#[description = "Replay file from Etterna's ReplaysV2 folder"]
+	replay_file: serenity::Attachment,
+	#[description = "BPM of the chart (default 120)"] bpm: Option<f32>,
+	#[description = "Judge to show info about"] judge: Option<Judge>,
+) -> Result<(), Error> {
+	let _typing = ctx.defer_or_broadcast().await;
+
+	crate::send_replay_file_card(ctx, &replay_file, bpm, judge.as_ref().map(|x| &x.0), false).await
+}
@@ -266,7 +266,7 @@ pub async fn details(
 	crate::send_score_card(
 		ctx,
 		crate::ScoreCard {
-			alternative_judge: judge.map(|x| x.0),
+			judges: judge.as_ref().map(|x| &x.0),
 			scorekey: &scorekey,
 			show_ssrs_and_judgements_and_modifiers: true,
 			user_id: None,
@@ -1,11 +1,18 @@
 //! All commands that show a skill graph image
 
-mod render;
+pub(super) mod render;
 
 use poise::serenity_prelude as serenity;
 
 use crate::{Context, Error};
 
+fn graph_attachment(png_bytes: Vec<u8>) -> serenity::AttachmentType<'static> {
+	serenity::AttachmentType::Bytes {
+		data: png_bytes.into(),
+		filename: "output.png".to_owned(),
+	}
+}
+
 fn parsedate(string: &str) -> chrono::NaiveDate {
 	chrono::NaiveDate::parse_from_str(string.trim(), "%Y-%m-%d %H:%M:%S")
 		.expect("Invalid date from EO")
@@ -186,15 +193,13 @@ async fn skillgraph_inner(
 	})
 	.await?;
 
-	if skill_timelines.len() == 1 {
-		render::draw_skillsets_graph(&skill_timelines[0], "output.png")
-			.map_err(|e| anyhow::anyhow!(e))?;
+	let graph = if skill_timelines.len() == 1 {
+		render::draw_skillsets_graph(&skill_timelines[0])?
 	} else {
-		render::draw_user_overalls_graph(&skill_timelines, &usernames, "output.png")
-			.map_err(|e| anyhow::anyhow!(e))?;
-	}
+		render::draw_user_overalls_graph(&skill_timelines, &usernames)?
+	};
 
-	poise::send_reply(ctx, |f| f.attachment("output.png".into())).await?;
+	poise::send_reply(ctx, |f| f.attachment(graph_attachment(graph))).await?;
 
 	Ok(())
 }
@@ -300,14 +305,12 @@ pub async fn accuracygraph(
 	let aaaaa_timeline =
 		calculate_skill_timeline(&scores, Some(etterna::Wifescore::AAAAA_THRESHOLD));
 
-	render::draw_accuracy_graph(
+	let graph = render::draw_accuracy_graph(
 		&full_timeline,
 		&aaa_timeline,
 		&aaaa_timeline,
 		&aaaaa_timeline,
-		"output.png",
-	)
-	.map_err(|e| anyhow::anyhow!(e))?;
+	)?;
 
 	let mut content = format!(
 		"Full rating: **{:.2}**",
@@ -325,7 +328,10 @@ pub async fn accuracygraph(
 			content += &format!("\n{}: **{:.2}**", name, rating.overall);
 		}
 	}
-	poise::send_reply(ctx, |f| f.content(content).attachment("output.png".into())).await?;
+	poise::send_reply(ctx, |f| {
+		f.content(content).attachment(graph_attachment(graph))
+	})
+	.await?;
 
 	Ok(())
 }
@@ -395,10 +401,10 @@ pub async fn scoregraph(
 		})
 		.await?;
 
-	render::draw_score_graph(&score_timelines, "output.png").map_err(|e| anyhow::anyhow!(e))?;
+	let graph = render::draw_score_graph(&score_timelines)?;
 
 	poise::send_reply(ctx, |f| {
-		f.attachment("output.png".into());
+		f.attachment(graph_attachment(graph));
 		if let [user] = &*score_timelines {
 			let mut content = format!(
 				"Number of sub-AAs: **{}**\nNumber of AAs: **{}**\nNumber of AAAs: **{}**\nNumber of AAAAs: **{}**\n",
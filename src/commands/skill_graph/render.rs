@@ -1,6 +1,9 @@
 //! Renders a graph via the plotters library
 
+use crate::render::rgb_buffer_to_png;
+use ::pattern as pattern_draw;
 use plotters::{
+	coord::Shift,
 	prelude::*,
 	style::{
 		text_anchor::{HPos, Pos, VPos},
@@ -30,6 +33,17 @@ fn parsedate(string: &str) -> chrono::NaiveDate {
 	chrono::NaiveDate::parse_from_str(string.trim(), "%Y-%m-%d").expect("Invalid date from EO")
 }
 
+fn white_text_style(pos: Pos) -> TextStyle<'static> {
+	TextStyle {
+		color: BackendColor {
+			rgb: (255, 255, 255),
+			alpha: 0.8,
+		},
+		pos,
+		font: ("Open Sans", 18).into(),
+	}
+}
+
 struct LineSpec<I> {
 	color: RGBAColor,
 	stroke_width: u32,
@@ -38,23 +52,37 @@ struct LineSpec<I> {
 	points: I,
 }
 
+const GRAPH_SIZE: (u32, u32) = (1280, 720);
+
+/// Runs the drawing function on an in-memory canvas and returns the result as PNG
+fn render_to_png(
+	draw: impl FnOnce(&DrawingArea<BitMapBackend<'_>, Shift>) -> Result<(), crate::Error>,
+) -> Result<Vec<u8>, crate::Error> {
+	let mut rgb_buffer = vec![0; GRAPH_SIZE.0 as usize * GRAPH_SIZE.1 as usize * 3];
+	{
+		let root = BitMapBackend::with_buffer(&mut rgb_buffer, GRAPH_SIZE).into_drawing_area();
+		draw(&root)?;
+		root.present()?;
+	}
+	rgb_buffer_to_png(rgb_buffer, GRAPH_SIZE)
+}
+
 fn generic_lines_over_time(
 	lines: &[LineSpec<impl IntoIterator<Item = (chrono::NaiveDate, f32)> + Clone>],
 	series_label_position: SeriesLabelPosition,
-	output_path: &str,
+) -> Result<Vec<u8>, crate::Error> {
+	render_to_png(|root| draw_lines_over_time(root, lines, series_label_position))
+}
+
+fn draw_lines_over_time(
+	root: &DrawingArea<BitMapBackend<'_>, Shift>,
+	lines: &[LineSpec<impl IntoIterator<Item = (chrono::NaiveDate, f32)> + Clone>],
+	series_label_position: SeriesLabelPosition,
 ) -> Result<(), crate::Error> {
 	assert!(lines.len() >= 1);
 
-	let label_text_style = TextStyle {
-		color: BackendColor {
-			rgb: (255, 255, 255),
-			alpha: 0.8,
-		},
-		pos: Pos::new(HPos::Center, VPos::Center),
-		font: ("Open Sans", 18).into(),
-	};
+	let label_text_style = white_text_style(Pos::new(HPos::Center, VPos::Center));
 
-	let root = BitMapBackend::new(output_path, (1280, 720)).into_drawing_area();
 	root.fill(&RGBColor(20, 20, 20))?;
 
 	// Find leftmost and rightmost x coordinate, as well as highest y coordinate
@@ -74,7 +102,7 @@ fn generic_lines_over_time(
 			.filter_map(|line| Some(line.points.clone().into_iter().last()?.1)),
 	);
 
-	let mut chart = ChartBuilder::on(&root)
+	let mut chart = ChartBuilder::on(root)
 		.x_label_area_size(25)
 		.y_label_area_size(35)
 		.margin(10)
@@ -123,14 +151,7 @@ fn generic_lines_over_time(
 		.configure_series_labels()
 		.position(series_label_position)
 		.background_style(&RGBColor(10, 10, 10).mix(0.8))
-		.label_font(TextStyle {
-			color: BackendColor {
-				rgb: (255, 255, 255),
-				alpha: 0.8,
-			},
-			pos: Pos::new(HPos::Left, VPos::Top),
-			font: ("Open Sans", 18).into(),
-		})
+		.label_font(white_text_style(Pos::new(HPos::Left, VPos::Top)))
 		.draw()?;
 
 	Ok(())
@@ -138,8 +159,7 @@ fn generic_lines_over_time(
 
 pub fn draw_skillsets_graph(
 	skill_timeline: &etterna::SkillTimeline<chrono::NaiveDate>,
-	output_path: &str,
-) -> Result<(), crate::Error> {
+) -> Result<Vec<u8>, crate::Error> {
 	let mut lines = Vec::new();
 	for ss in etterna::Skillset8::iter() {
 		lines.push(LineSpec {
@@ -157,14 +177,13 @@ pub fn draw_skillsets_graph(
 		});
 	}
 
-	generic_lines_over_time(&lines, SeriesLabelPosition::MiddleRight, output_path)
+	generic_lines_over_time(&lines, SeriesLabelPosition::MiddleRight)
 }
 
 pub fn draw_user_overalls_graph(
 	skill_timelines: &[etterna::SkillTimeline<chrono::NaiveDate>],
 	usernames: &[&str],
-	output_path: &str,
-) -> Result<(), crate::Error> {
+) -> Result<Vec<u8>, crate::Error> {
 	assert_eq!(skill_timelines.len(), usernames.len());
 
 	const COLOR_MAP: &[RGBColor] = &[
@@ -196,7 +215,7 @@ pub fn draw_user_overalls_graph(
 		});
 	}
 
-	generic_lines_over_time(&lines, SeriesLabelPosition::MiddleRight, output_path)
+	generic_lines_over_time(&lines, SeriesLabelPosition::MiddleRight)
 }
 
 pub fn draw_accuracy_graph(
@@ -204,8 +223,7 @@ pub fn draw_accuracy_graph(
 	aaa_timeline: &etterna::SkillTimeline<&str>,
 	aaaa_timeline: &etterna::SkillTimeline<&str>,
 	aaaaa_timeline: &etterna::SkillTimeline<&str>,
-	output_path: &str,
-) -> Result<(), crate::Error> {
+) -> Result<Vec<u8>, crate::Error> {
 	let mut lines = Vec::new();
 	for (skill_timeline, name, color) in &[
 		(full_timeline, "All scores", RGBColor(0x66, 0xCC, 0x66)),
@@ -228,7 +246,7 @@ pub fn draw_accuracy_graph(
 		});
 	}
 
-	generic_lines_over_time(&lines, SeriesLabelPosition::MiddleRight, output_path)
+	generic_lines_over_time(&lines, SeriesLabelPosition::MiddleRight)
 }
 
 pub struct ScoreGraphUser {
@@ -249,7 +267,7 @@ fn lerp_color(a: RGBColor, b: RGBColor, t: f32) -> RGBColor {
 	)
 }
 
-pub fn draw_score_graph(users: &[ScoreGraphUser], output_path: &str) -> Result<(), crate::Error> {
+pub fn draw_score_graph(users: &[ScoreGraphUser]) -> Result<Vec<u8>, crate::Error> {
 	assert!(!users.is_empty());
 
 	const COLOR_MAP: &[RGBColor] = &[
@@ -331,5 +349,84 @@ pub fn draw_score_graph(users: &[ScoreGraphUser], output_path: &str) -> Result<(
 		}
 	}
 
-	generic_lines_over_time(&lines, SeriesLabelPosition::MiddleLeft, output_path)
+	generic_lines_over_time(&lines, SeriesLabelPosition::MiddleLeft)
+}
+
+/// Draws the notes per second of a pattern over time, in total, chord-weighted and for each hand
+pub fn draw_density_graph(density: &pattern_draw::PatternDensity) -> Result<Vec<u8>, crate::Error> {
+	render_to_png(|root| draw_density(root, density))
+}
+
+fn draw_density(
+	root: &DrawingArea<BitMapBackend<'_>, Shift>,
+	density: &pattern_draw::PatternDensity,
+) -> Result<(), crate::Error> {
+	let label_text_style = white_text_style(Pos::new(HPos::Center, VPos::Center));
+
+	root.fill(&RGBColor(20, 20, 20))?;
+
+	// Leave some room above the peak for the legend
+	let upper_bound = density.peak_nps() * 1.2;
+	let mut chart = ChartBuilder::on(root)
+		.x_label_area_size(25)
+		.y_label_area_size(35)
+		.margin(10)
+		.build_cartesian_2d(0.0..density.duration, 0.0..upper_bound)?;
+
+	// Short patterns would only have a few labels when labeled with whole seconds
+	let x_label_formatter = |&seconds: &f32| {
+		if density.duration < 10.0 {
+			format!("{:.1}s", seconds)
+		} else {
+			format!("{}:{:02}", seconds as u32 / 60, seconds as u32 % 60)
+		}
+	};
+	chart
+		.configure_mesh()
+		.bold_line_style(&WHITE.mix(0.3))
+		.light_line_style(&TRANSPARENT)
+		.axis_style(&WHITE.mix(0.5))
+		.x_label_style(label_text_style.clone())
+		.x_label_formatter(&x_label_formatter)
+		.y_label_style(label_text_style.clone())
+		.y_label_formatter(&|nps| format!("{:.0}", nps))
+		.draw()?;
+
+	let lines: [(&str, RGBColor, u32, fn(&pattern_draw::DensityPoint) -> f32); 4] = [
+		("Left hand", RGBColor(0x66, 0xCC, 0xFF), 1, |p| {
+			p.left_hand_nps
+		}),
+		("Right hand", RGBColor(0xDA, 0x57, 0x57), 1, |p| {
+			p.right_hand_nps
+		}),
+		("Chord-weighted", RGBColor(0xEE, 0xBB, 0x00), 2, |p| {
+			p.chord_weighted_nps
+		}),
+		("Notes per second", RGBColor(0xFF, 0xFF, 0xFF), 3, |p| p.nps),
+	];
+	for (label, color, stroke_width, value) in lines {
+		let shape_style = ShapeStyle {
+			color: color.to_rgba(),
+			stroke_width,
+			filled: true,
+		};
+		chart
+			.draw_series(LineSeries::new(
+				density.points.iter().map(|p| (p.time, value(p))),
+				shape_style.clone(),
+			))?
+			.label(label)
+			.legend(move |(x, y)| {
+				plotters::element::Circle::new((x + 10, y), 5, shape_style.clone())
+			});
+	}
+
+	chart
+		.configure_series_labels()
+		.position(SeriesLabelPosition::UpperRight)
+		.background_style(&RGBColor(10, 10, 10).mix(0.8))
+		.label_font(white_text_style(Pos::new(HPos::Left, VPos::Top)))
+		.draw()?;
+
+	Ok(())
 }
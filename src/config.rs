@@ -32,6 +32,10 @@ pub struct Config {
 	/// Channels in which bot commands can be used
 	pub allowed_channels: Vec<serenity::ChannelId>,
 	pub etterna_online_guild_id: serenity::GuildId,
+	/// Directory with .sm and .ssc files for the chart command, in any folder structure (e.g. an
+	/// Etterna Songs folder). A `chartkeys.json` inside maps chartkeys to chart files
+	#[serde(default)]
+	pub chart_library_directory: Option<std::path::PathBuf>,
 }
 
 impl Config {
@@ -67,11 +71,34 @@ pub struct ScoresList {
 	pub username: String,
 }
 
+/// Defaults for the pattern command, which apply unless overridden in the command invocation
+#[derive(Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PatternPreferences {
+	pub noteskin: Option<String>,
+	/// Overrides `noteskin` in specific keymodes
+	#[serde(default)]
+	pub keymode_noteskins: HashMap<u32, String>,
+	/// Snap number, e.g. 16 for 16ths
+	pub snap: Option<u32>,
+	pub zoom: Option<f32>,
+}
+
+impl PatternPreferences {
+	pub fn noteskin(&self, keymode: u32) -> Option<&str> {
+		self.keymode_noteskins
+			.get(&keymode)
+			.or(self.noteskin.as_ref())
+			.map(String::as_str)
+	}
+}
+
 #[derive(Default, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Data {
 	pub user_registry: Vec<UserRegistryEntry>,
 	rival_mapping: HashMap<serenity::UserId, String>, // discord username -> eo username
 	preferred_scroll: HashMap<serenity::UserId, etterna::ScrollDirection>,
+	#[serde(default)]
+	pattern_preferences: HashMap<serenity::UserId, PatternPreferences>,
 	pub last_scores_list: HashMap<serenity::ChannelId, ScoresList>,
 }
 
@@ -102,10 +129,29 @@ impl Data {
 		self.preferred_scroll.insert(discord_user, scroll);
 	}
 
+	pub fn remove_scroll(&mut self, discord_user: serenity::UserId) {
+		self.preferred_scroll.remove(&discord_user);
+	}
+
 	pub fn scroll(&self, discord_user: serenity::UserId) -> Option<etterna::ScrollDirection> {
 		self.preferred_scroll.get(&discord_user).copied()
 	}
 
+	pub fn set_pattern_preferences(
+		&mut self,
+		discord_user: serenity::UserId,
+		preferences: PatternPreferences,
+	) {
+		self.pattern_preferences.insert(discord_user, preferences);
+	}
+
+	pub fn pattern_preferences(&self, discord_user: serenity::UserId) -> PatternPreferences {
+		self.pattern_preferences
+			.get(&discord_user)
+			.cloned()
+			.unwrap_or_default()
+	}
+
 	pub fn set_rival(&mut self, discord_user: serenity::UserId, rival: String) -> Option<String> {
 		self.rival_mapping.insert(discord_user, rival)
 	}
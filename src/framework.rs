@@ -82,6 +82,17 @@ async fn listener(
 	match event {
 		poise::Event::Message { new_message } => {
 			let invocation_data = tokio::sync::Mutex::new(Box::new(()) as _);
+			let is_command_invocation = matches!(
+				poise::parse_invocation(
+					ctx,
+					new_message,
+					framework,
+					&invocation_data,
+					poise::MessageDispatchTrigger::MessageCreate,
+				)
+				.await,
+				Ok(Some(_))
+			);
 			let ctx = poise::PrefixContext {
 				data: state,
 				serenity_context: ctx,
@@ -102,6 +113,7 @@ async fn listener(
 				ctx,
 				user_has_manage_messages_permission(poise::Context::Prefix(ctx)).await?,
 				user_is_allowed_bot_interaction(poise::Context::Prefix(ctx)).await?,
+				is_command_invocation,
 			)
 			.await
 		}
@@ -149,6 +161,8 @@ pub async fn run_framework(auth: crate::Auth, discord_bot_token: &str) -> Result
 				commands::help(),
 				commands::profile(),
 				commands::pattern(),
+				commands::chart(),
+				commands::density(),
 				commands::ping(),
 				commands::servers(),
 				commands::uptime(),
@@ -156,9 +170,11 @@ pub async fn run_framework(auth: crate::Auth, discord_bot_token: &str) -> Result
 				commands::randomscore(),
 				commands::lookup(),
 				commands::scrollset(),
+				commands::patternset(),
 				commands::userset(),
 				commands::rivalset(),
 				commands::rs(),
+				commands::replay(),
 				commands::rival(),
 				commands::skillgraph(),
 				commands::rivalgraph(),
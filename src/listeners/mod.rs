@@ -49,7 +49,7 @@ fn extract_score_links_from_string(
 }
 
 async fn show_score_links_inside_message(ctx: PrefixContext<'_>) {
-	let alternative_judge = crate::extract_judge_from_string(&ctx.msg.content);
+	let judges = crate::extract_judge_from_string(&ctx.msg.content);
 	let mut score_links = extract_score_links_from_string(&ctx.msg.content);
 	if let Some((scorekey, user_id)) = score_links.next() {
 		log::info!(
@@ -64,7 +64,7 @@ async fn show_score_links_inside_message(ctx: PrefixContext<'_>) {
 				user_id: None,
 				username: None,
 				show_ssrs_and_judgements_and_modifiers: true,
-				alternative_judge,
+				judges: judges.as_ref(),
 				draw_mean_instead_of_wifescore: ctx.msg.content.contains("mean"),
 			},
 		)
@@ -80,10 +80,46 @@ async fn show_score_links_inside_message(ctx: PrefixContext<'_>) {
 	}
 }
 
+/// Replay files in Etterna's ReplaysV2 folder are named after the scorekey of their score
+async fn show_replay_files_inside_message(ctx: PrefixContext<'_>) {
+	let replay_file = match ctx
+		.msg
+		.attachments
+		.iter()
+		.find(|attachment| etterna::Scorekey::new(attachment.filename.clone()).is_some())
+	{
+		Some(x) => x,
+		None => return,
+	};
+
+	log::info!(
+		"Trying to show replay file card for {}",
+		replay_file.filename
+	);
+	let judges = crate::extract_judge_from_string(&ctx.msg.content);
+	if let Err(e) = crate::send_replay_file_card(
+		poise::Context::Prefix(ctx),
+		replay_file,
+		None,
+		judges.as_ref(),
+		ctx.msg.content.contains("mean"),
+	)
+	.await
+	{
+		log::warn!(
+			"Error while showing replay file card for {}: {}",
+			replay_file.filename,
+			e
+		);
+	}
+}
+
+/// `is_command_invocation` is whether the framework also runs a command for this message
 pub async fn listen_message(
 	ctx: PrefixContext<'_>,
 	has_manage_messages_permission: bool,
 	user_is_allowed_bot_interaction: bool,
+	is_command_invocation: bool,
 ) -> Result<(), Error> {
 	if ctx.msg.channel_id == ctx.data.config.work_in_progress_channel
 		&& !has_manage_messages_permission
@@ -127,6 +163,10 @@ pub async fn listen_message(
 
 	if user_is_allowed_bot_interaction {
 		show_score_links_inside_message(ctx).await;
+		// Otherwise `+replay` would be answered twice
+		if !is_command_invocation {
+			show_replay_files_inside_message(ctx).await;
+		}
 	}
 
 	Ok(())
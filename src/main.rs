@@ -31,6 +31,8 @@ use cached::Cached;
 
 mod easter_egg;
 
+mod render;
+
 // Custom serenity prelude module
 use poise::serenity_prelude as serenity;
 
@@ -97,32 +99,132 @@ fn country_code_to_flag_emoji(country_code: &str) -> String {
 	inner(country_code).unwrap_or_else(|| "❓".into())
 }
 
-fn extract_judge_from_string(string: &str) -> Option<&'static etterna::Judge> {
+/// Etterna scales the J4 timing windows by these factors for J1 to J9
+const JUDGE_SCALES: [f32; 9] = [1.50, 1.33, 1.16, 1.00, 0.84, 0.66, 0.50, 0.33, 0.20];
+/// Etterna never makes a timing window wider than this, in seconds
+const MAX_TIMING_WINDOW: f32 = 0.18;
+
+fn all_judges() -> [&'static etterna::Judge; 9] {
+	[
+		etterna::J1,
+		etterna::J2,
+		etterna::J3,
+		etterna::J4,
+		etterna::J5,
+		etterna::J6,
+		etterna::J7,
+		etterna::J8,
+		etterna::J9,
+	]
+}
+
+/// A judge with the given marvelous, perfect, great, good and bad windows in seconds. Hold and mine
+/// windows stay the same as on J4
+fn judge_with_windows(windows: [f32; 5]) -> etterna::Judge {
+	let [marvelous, perfect, great, good, bad] = windows;
+	etterna::Judge {
+		marvelous_window: marvelous.min(MAX_TIMING_WINDOW),
+		perfect_window: perfect.min(MAX_TIMING_WINDOW),
+		great_window: great.min(MAX_TIMING_WINDOW),
+		good_window: good.min(MAX_TIMING_WINDOW),
+		bad_window: bad.min(MAX_TIMING_WINDOW),
+		..etterna::J4.clone()
+	}
+}
+
+/// The J4 windows scaled by the given factor, like Etterna does for the other judges
+fn scaled_judge(scale: f32) -> etterna::Judge {
+	let j4 = etterna::J4;
+	judge_with_windows([
+		j4.marvelous_window * scale,
+		j4.perfect_window * scale,
+		j4.great_window * scale,
+		j4.good_window * scale,
+		j4.bad_window * scale,
+	])
+}
+
+/// Parses a judge like `J7` or `J4.5`. Judges between two whole judges scale the timing windows
+/// by a factor between theirs
+fn parse_judge(judge_num: f32) -> Option<AlternativeJudge> {
+	if !(1.0..=9.0).contains(&judge_num) {
+		return None;
+	}
+
+	let lower_judge = judge_num.floor() as usize;
+	let judge = if judge_num.fract() == 0.0 {
+		all_judges()[lower_judge - 1].clone()
+	} else {
+		let lower_scale = JUDGE_SCALES[lower_judge - 1];
+		let upper_scale = JUDGE_SCALES[lower_judge];
+		scaled_judge(lower_scale + (upper_scale - lower_scale) * judge_num.fract())
+	};
+	Some(AlternativeJudge {
+		name: format!("J{}", judge_num),
+		judge,
+	})
+}
+
+/// Parses timing windows in milliseconds: either all five like `22.5/45/90/135/180`, or just the
+/// marvelous window, which the other windows are scaled along with
+fn parse_judge_windows(windows_ms: &str) -> Option<AlternativeJudge> {
+	let windows = windows_ms
+		.split('/')
+		.map(|window| match window.parse::<f32>() {
+			Ok(window) if window > 0.0 => Some(window / 1000.0),
+			_ => None,
+		})
+		.collect::<Option<Vec<_>>>()?;
+
+	let judge = match *windows {
+		[marvelous] => scaled_judge(marvelous / etterna::J4.marvelous_window),
+		[marvelous, perfect, great, good, bad]
+			if windows.windows(2).all(|pair| pair[0] < pair[1]) =>
+		{
+			judge_with_windows([marvelous, perfect, great, good, bad])
+		}
+		_ => return None,
+	};
+	Some(AlternativeJudge {
+		name: format!("{}ms", windows_ms),
+		judge,
+	})
+}
+
+/// Finds a judge like `J7`, `J4.5`, `judge 15ms` or `windows 22.5/45/90/135/180ms`, or a request
+/// for all judges like `all judges` or `J1-J9`, anywhere in the string. Timing windows need the
+/// `judge` or `windows` in front, so that other numbers in milliseconds aren't taken for a judge
+fn extract_judge_from_string(string: &str) -> Option<JudgeSelection> {
+	static ALL_JUDGES_REGEX: once_cell::sync::Lazy<regex::Regex> =
+		once_cell::sync::Lazy::new(|| {
+			regex::Regex::new(r"(?i)\ball\s*judges\b|\bj1\s*-\s*j?9\b").unwrap()
+		});
 	static JUDGE_REGEX: once_cell::sync::Lazy<regex::Regex> =
-		once_cell::sync::Lazy::new(|| regex::Regex::new(r"[jJ](\d)").unwrap());
+		once_cell::sync::Lazy::new(|| regex::Regex::new(r"[jJ](\d(?:\.\d+)?)").unwrap());
+	static JUDGE_WINDOWS_REGEX: once_cell::sync::Lazy<regex::Regex> =
+		once_cell::sync::Lazy::new(|| {
+			regex::Regex::new(
+				r"(?i)\b(?:judge|windows?)\s*(\d+(?:\.\d+)?(?:/\d+(?:\.\d+)?)*)\s*ms\b",
+			)
+			.unwrap()
+		});
+
+	if ALL_JUDGES_REGEX.is_match(string) {
+		return Some(JudgeSelection::All);
+	}
 
 	JUDGE_REGEX
 		.captures_iter(string)
-		.filter_map(|groups| {
-			// UNWRAP: the regex definition contains a group
-			let judge_num_string = groups.get(1).unwrap().as_str();
-
-			let judge_num: u32 = judge_num_string.parse().ok()?;
-
-			match judge_num {
-				1 => Some(etterna::J1),
-				2 => Some(etterna::J2),
-				3 => Some(etterna::J3),
-				4 => Some(etterna::J4),
-				5 => Some(etterna::J5),
-				6 => Some(etterna::J6),
-				7 => Some(etterna::J7),
-				8 => Some(etterna::J8),
-				9 => Some(etterna::J9),
-				_ => None,
-			}
-		})
+		// UNWRAP: the regex definition contains a group
+		.filter_map(|groups| parse_judge(groups.get(1).unwrap().as_str().parse().ok()?))
+		.chain(
+			JUDGE_WINDOWS_REGEX
+				.captures_iter(string)
+				// UNWRAP: the regex definition contains a group
+				.filter_map(|groups| parse_judge_windows(groups.get(1).unwrap().as_str())),
+		)
 		.next()
+		.map(JudgeSelection::Single)
 }
 
 /// Transforms an error by checking, if it's a User Not Found error. If yes,
@@ -0,0 +1,19 @@
+//! Image encoding shared by the graphs of the score card and the graph commands
+
+use crate::Error;
+
+/// Encodes the RGB pixels that plotters' `BitMapBackend::with_buffer` draws into as PNG
+pub fn rgb_buffer_to_png(
+	rgb_buffer: Vec<u8>,
+	(width, height): (u32, u32),
+) -> Result<Vec<u8>, Error> {
+	let image = image::RgbImage::from_raw(width, height, rgb_buffer)
+		.ok_or_else(|| anyhow::anyhow!("Plot buffer doesn't match the image size"))?;
+
+	let mut png_bytes = Vec::new();
+	image::DynamicImage::ImageRgb8(image).write_to(
+		&mut std::io::Cursor::new(&mut png_bytes),
+		image::ImageOutputFormat::Png,
+	)?;
+	Ok(png_bytes)
+}
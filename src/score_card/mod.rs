@@ -1,23 +1,45 @@
 //! Utility code used by various parts of the bot to show a score card
 
 mod replay_analysis;
+mod replay_file;
 mod replay_graph;
 
 use crate::{serenity, Context, Error};
 
+const REPLAY_GRAPH_FILENAME: &str = "replay_graph.png";
+/// Replay files don't store the BPM of the chart, so note times are calculated with this unless
+/// the user specifies one
+const DEFAULT_REPLAY_FILE_BPM: f32 = 120.0;
+/// Even replays of very long charts are only a few hundred kilobytes
+const MAX_REPLAY_FILE_SIZE: u64 = 5_000_000;
+
+/// A judge that a score is rescored with in addition to J4
+pub struct AlternativeJudge {
+	/// For example `J7`, `J4.5` or `15ms`
+	pub name: String,
+	pub judge: etterna::Judge,
+}
+
+/// The judges which a score card shows the score on, besides J4
+pub enum JudgeSelection {
+	Single(AlternativeJudge),
+	/// A table of the score on every judge from J1 to J9
+	All,
+}
+
 pub struct ScoreCard<'a> {
 	pub scorekey: &'a etterna::Scorekey,
 	pub user_id: Option<u32>,      // pass None if score link shouldn't be shown
 	pub username: Option<&'a str>, // used to detect scorekey collision
 	pub show_ssrs_and_judgements_and_modifiers: bool,
-	pub alternative_judge: Option<&'a etterna::Judge>,
+	pub judges: Option<&'a JudgeSelection>,
 	pub draw_mean_instead_of_wifescore: bool,
 }
 
 fn write_score_card_body(
 	info: &ScoreCard<'_>,
 	score: &etternaonline_api::v1::ScoreData,
-	alternative_judge_wifescore: Option<etterna::Wifescore>,
+	alternative_judge_wifescore: Option<(&AlternativeJudge, etterna::Wifescore)>,
 ) -> String {
 	let mut description = String::new();
 
@@ -39,15 +61,16 @@ fn write_score_card_body(
 	}
 
 	description += "```nim\n";
-	description += &if let Some(alternative_judge_wifescore) = alternative_judge_wifescore {
+	description += &if let Some((alternative_judge, alternative_judge_wifescore)) =
+		alternative_judge_wifescore
+	{
 		format!(
 			concat!(
 				"        Wife: {:<5.2}%  ⏐\n",
 				"     Wife {}: {:<5.2}%  ⏐      Marvelous: {}",
 			),
 			score.wifescore.as_percent(),
-			// UWNRAP: if alternative_judge_wifescore is Some, info.alternative_judge is too
-			info.alternative_judge.unwrap().name,
+			alternative_judge.name,
 			alternative_judge_wifescore.as_percent(),
 			score.judgements.marvelouses,
 		)
@@ -94,10 +117,11 @@ fn write_score_card_body(
 	description
 }
 
+/// `reported_wifescore` is the score that Etterna calculated, if known
 fn generate_score_comparisons_text(
-	score: &etternaonline_api::v1::ScoreData,
+	reported_wifescore: Option<etterna::Wifescore>,
 	analysis: &replay_analysis::ReplayAnalysis,
-	alternative_judge: Option<&etterna::Judge>,
+	alternative_judge: Option<&AlternativeJudge>,
 ) -> String {
 	let wifescore_floating_point_digits = match analysis
 		.scoring_system_comparison_j4
@@ -113,7 +137,7 @@ fn generate_score_comparisons_text(
 	let alternative_text_2;
 	let alternative_text_4;
 	if let Some(comparison) = &analysis.scoring_system_comparison_alternative {
-		// UNWRAP: if we're in this branch, info.alternative_judge is Some
+		// UNWRAP: if we're in this branch, alternative_judge is Some
 		alternative_text_1 = format!(
 			", {:.digits$} on {}",
 			comparison.wife2_score,
@@ -140,14 +164,16 @@ fn generate_score_comparisons_text(
 
 	let mut score_comparisons_text = String::new();
 
-	if (analysis
-		.scoring_system_comparison_j4
-		.wife3_score
-		.as_percent()
-		- score.wifescore.as_percent())
-	.abs() > 0.01
-	{
-		score_comparisons_text += "_Note: these calculated scores are slightly inaccurate_\n";
+	if let Some(reported_wifescore) = reported_wifescore {
+		if (analysis
+			.scoring_system_comparison_j4
+			.wife3_score
+			.as_percent()
+			- reported_wifescore.as_percent())
+		.abs() > 0.01
+		{
+			score_comparisons_text += "_Note: these calculated scores are slightly inaccurate_\n";
+		}
 	}
 
 	score_comparisons_text += &format!(
@@ -177,29 +203,154 @@ fn generate_score_comparisons_text(
 	score_comparisons_text
 }
 
+fn generate_all_judges_text(
+	all_judges: &[(String, replay_analysis::ScoringSystemComparison)],
+) -> String {
+	let mut text = "```\nJudge   Wife2   Wife3  Wife3 (mean corrected)\n".to_owned();
+	for (name, comparison) in all_judges {
+		text += &format!(
+			"{:<5} {:>6.2}% {:>6.2}% {:>6.2}%\n",
+			name,
+			comparison.wife2_score.as_percent(),
+			comparison.wife3_score.as_percent(),
+			comparison.wife3_score_zero_mean.as_percent(),
+		);
+	}
+	text += "```";
+	text
+}
+
+fn generate_columns_text(columns: &[Option<replay_analysis::ColumnAnalysis>]) -> String {
+	let mut text = "```\nCol  Marv  Perf Great Good  Bad Miss   Mean  StdDev   Wife\n".to_owned();
+	for (i, column) in columns.iter().enumerate() {
+		let column = match column {
+			Some(column) => column,
+			None => {
+				text += &format!(
+					"{:>3} {:>5} {:>5} {:>5} {:>4} {:>4} {:>4} {:>6} {:>7} {:>6}\n",
+					i + 1,
+					"-",
+					"-",
+					"-",
+					"-",
+					"-",
+					"-",
+					"-",
+					"-",
+					"-",
+				);
+				continue;
+			}
+		};
+		let [marvelouses, perfects, greats, goods, bads, misses] = column.judgement_counts;
+		text += &format!(
+			"{:>3} {:>5} {:>5} {:>5} {:>4} {:>4} {:>4} {:>+6.1} {:>7.1} {:>5.2}%\n",
+			i + 1,
+			marvelouses,
+			perfects,
+			greats,
+			goods,
+			bads,
+			misses,
+			column.mean_offset * 1000.0,
+			column.standard_deviation * 1000.0,
+			column.wifescore.as_percent(),
+		);
+	}
+	text += "```";
+	text
+}
+
+fn add_replay_analysis_fields(
+	embed: &mut serenity::CreateEmbed,
+	analysis: &replay_analysis::ReplayAnalysis,
+	alternative_judge: Option<&AlternativeJudge>,
+	reported_wifescore: Option<etterna::Wifescore>,
+) {
+	embed
+		.attachment(REPLAY_GRAPH_FILENAME)
+		.field(
+			"Score comparisons",
+			generate_score_comparisons_text(reported_wifescore, analysis, alternative_judge),
+			false,
+		)
+		.field(
+			"Tap speeds",
+			format!(
+				"\
+Fastest jack over a course of 20 notes: {:.2} NPS
+Fastest total NPS over a course of 100 notes: {:.2} NPS",
+				analysis.fastest_finger_jackspeed, analysis.fastest_nps,
+			),
+			false,
+		)
+		.field(
+			"Combos",
+			format!(
+				"\
+Longest combo: {}
+Longest perfect combo: {}
+Longest marvelous combo: {}
+Longest 100% combo: {}
+",
+				analysis.longest_combo,
+				analysis.longest_perf_combo,
+				analysis.longest_marv_combo,
+				analysis.longest_100_combo,
+			),
+			false,
+		);
+
+	if let Some(all_judges) = &analysis.all_judges {
+		embed.field("All judges", generate_all_judges_text(all_judges), false);
+	}
+	if let Some(columns) = &analysis.columns {
+		embed.field(
+			"Columns (mean and standard deviation in ms)",
+			generate_columns_text(columns),
+			false,
+		);
+	}
+}
+
 pub async fn send_score_card(ctx: Context<'_>, info: ScoreCard<'_>) -> Result<(), Error> {
 	let score = ctx.data().v1.score_data(info.scorekey).await?;
 
-	let alternative_judge_wifescore = match (info.alternative_judge, &score.replay) {
+	let alternative_judge = match info.judges {
+		Some(JudgeSelection::Single(alternative_judge)) => Some(alternative_judge),
+		Some(JudgeSelection::All) | None => None,
+	};
+	let show_all_judges = matches!(info.judges, Some(JudgeSelection::All));
+
+	let alternative_judge_wifescore = match (alternative_judge, &score.replay) {
 		(Some(alternative_judge), Some(replay)) => {
 			etterna::rescore_from_note_hits::<etterna::Wife3, _>(
 				replay.notes.iter().map(|note| note.hit),
 				score.judgements.hit_mines,
 				score.judgements.let_go_holds + score.judgements.missed_holds,
-				alternative_judge,
+				&alternative_judge.judge,
 			)
+			.map(|wifescore| (alternative_judge, wifescore))
 		}
 		_ => None,
 	};
 
 	let description = write_score_card_body(&info, &score, alternative_judge_wifescore);
 
-	let replay_analysis = replay_analysis::do_replay_analysis(
-		&score,
-		info.alternative_judge,
-		info.draw_mean_instead_of_wifescore,
-	)
-	.transpose()?;
+	let replay_analysis = score
+		.replay
+		.as_ref()
+		.and_then(|replay| {
+			replay_analysis::do_replay_analysis(
+				replay,
+				score.judgements.hit_mines,
+				score.judgements.let_go_holds + score.judgements.missed_holds,
+				alternative_judge,
+				info.draw_mean_instead_of_wifescore,
+				show_all_judges,
+			)
+		})
+		.transpose()?;
 
 	let mut embed = serenity::CreateEmbed::default();
 	embed
@@ -231,48 +382,32 @@ pub async fn send_score_card(ctx: Context<'_>, info: ScoreCard<'_>) -> Result<()
 		});
 
 	if let Some(analysis) = &replay_analysis {
-		embed
-			.attachment(analysis.replay_graph_path)
-			.field(
-				"Score comparisons",
-				generate_score_comparisons_text(&score, analysis, info.alternative_judge),
-				false,
-			)
-			.field(
-				"Tap speeds",
-				format!(
-					"\
-Fastest jack over a course of 20 notes: {:.2} NPS
-Fastest total NPS over a course of 100 notes: {:.2} NPS",
-					analysis.fastest_finger_jackspeed, analysis.fastest_nps,
-				),
-				false,
-			)
-			.field(
-				"Combos",
-				format!(
-					"\
-Longest combo: {}
-Longest perfect combo: {}
-Longest marvelous combo: {}
-Longest 100% combo: {}
-",
-					analysis.longest_combo,
-					analysis.longest_perf_combo,
-					analysis.longest_marv_combo,
-					analysis.longest_100_combo,
-				),
-				false,
-			);
+		add_replay_analysis_fields(
+			&mut embed,
+			analysis,
+			alternative_judge,
+			Some(score.wifescore),
+		);
 	}
 
+	send_embed_with_replay_graph(ctx, embed, replay_analysis.map(|a| a.replay_graph)).await
+}
+
+async fn send_embed_with_replay_graph(
+	ctx: Context<'_>,
+	embed: serenity::CreateEmbed,
+	replay_graph: Option<Vec<u8>>,
+) -> Result<(), Error> {
 	poise::send_reply(ctx, |f: &mut poise::CreateReply<'_>| {
 		f.embed(|e| {
 			*e = embed;
 			e
 		});
-		if let Some(analysis) = &replay_analysis {
-			f.attachment(analysis.replay_graph_path.into());
+		if let Some(replay_graph) = replay_graph {
+			f.attachment(serenity::AttachmentType::Bytes {
+				data: replay_graph.into(),
+				filename: REPLAY_GRAPH_FILENAME.to_owned(),
+			});
 		}
 		f
 	})
@@ -280,3 +415,74 @@ Longest 100% combo: {}
 
 	Ok(())
 }
+
+/// Shows the replay analysis of an attached replay file, for scores that aren't on EO. There's no
+/// song, player or SSR info in a replay file, so the card is made up of the analysis only
+pub async fn send_replay_file_card(
+	ctx: Context<'_>,
+	attachment: &serenity::Attachment,
+	bpm: Option<f32>,
+	judges: Option<&JudgeSelection>,
+	draw_mean_instead_of_wifescore: bool,
+) -> Result<(), Error> {
+	let bpm = bpm.unwrap_or(DEFAULT_REPLAY_FILE_BPM);
+	if !(bpm > 0.0 && bpm.is_finite()) {
+		return Err(anyhow::anyhow!("The BPM must be a positive number"));
+	}
+	if attachment.size > MAX_REPLAY_FILE_SIZE {
+		return Err(anyhow::anyhow!("That file is too big to be a replay"));
+	}
+	let replay_file = attachment.download().await?;
+	let replay_file = replay_file::parse_replay_file(&String::from_utf8_lossy(&replay_file), bpm)?;
+
+	let alternative_judge = match judges {
+		Some(JudgeSelection::Single(alternative_judge)) => Some(alternative_judge),
+		Some(JudgeSelection::All) | None => None,
+	};
+	let show_all_judges = matches!(judges, Some(JudgeSelection::All));
+
+	let analysis = replay_analysis::do_replay_analysis(
+		&replay_file.replay,
+		replay_file.num_hit_mines,
+		replay_file.num_dropped_holds,
+		alternative_judge,
+		draw_mean_instead_of_wifescore,
+		show_all_judges,
+	)
+	.ok_or_else(|| anyhow::anyhow!("Couldn't analyze this replay"))??;
+
+	let mut judgement_counts = [0; 6];
+	for column in analysis.columns.iter().flatten().flatten() {
+		for (total, count) in judgement_counts.iter_mut().zip(column.judgement_counts) {
+			*total += count;
+		}
+	}
+	let [marvelouses, perfects, greats, goods, bads, misses] = judgement_counts;
+
+	let mut embed = serenity::CreateEmbed::default();
+	embed
+		.color(crate::ETTERNA_COLOR)
+		.title(format!("Replay {}", attachment.filename))
+		.description(format!(
+			"\
+_Note times and speeds assume a constant {} BPM_
+```nim
+Marvelous: {:<6} Good: {}
+  Perfect: {:<6}  Bad: {}
+    Great: {:<6} Miss: {}
+Hit Mines: {:<6} Dropped Holds: {}
+```",
+			bpm,
+			marvelouses,
+			goods,
+			perfects,
+			bads,
+			greats,
+			misses,
+			replay_file.num_hit_mines,
+			replay_file.num_dropped_holds,
+		));
+	add_replay_analysis_fields(&mut embed, &analysis, alternative_judge, None);
+
+	send_embed_with_replay_graph(ctx, embed, Some(analysis.replay_graph)).await
+}
@@ -10,8 +10,21 @@ pub struct ScoringSystemComparison {
 	pub wife3_score_zero_mean: etterna::Wifescore,
 }
 
+/// Accuracy of the taps in a single column, judged on J4
+pub struct ColumnAnalysis {
+	/// Number of marvelouses, perfects, greats, goods, bads and misses, in that order
+	pub judgement_counts: [u32; 6],
+	/// Mean deviation in seconds of all notes that were hit
+	pub mean_offset: f32,
+	/// Standard deviation in seconds of all notes that were hit
+	pub standard_deviation: f32,
+	/// Wife3 score of only the taps in this column. Mines and holds aren't counted
+	pub wifescore: etterna::Wifescore,
+}
+
 pub struct ReplayAnalysis {
-	pub replay_graph_path: &'static str,
+	/// PNG image of the offsets and the wifescore or mean over time, and the offset histogram
+	pub replay_graph: Vec<u8>,
 	pub scoring_system_comparison_j4: ScoringSystemComparison,
 	pub scoring_system_comparison_alternative: Option<ScoringSystemComparison>,
 	pub fastest_finger_jackspeed: f32, // NPS, single finger
@@ -21,6 +34,11 @@ pub struct ReplayAnalysis {
 	pub longest_perf_combo: u32,
 	pub longest_combo: u32,
 	pub mean_offset: f32,
+	/// The score on every judge from J1 to J9, if requested
+	pub all_judges: Option<Vec<(String, ScoringSystemComparison)>>,
+	/// None if the replay doesn't say which column each note is in. Contains None for columns
+	/// without notes
+	pub columns: Option<Vec<Option<ColumnAnalysis>>>,
 }
 
 fn fastest_nps(replay: &etternaonline_api::Replay) -> Option<f32> {
@@ -57,6 +75,73 @@ fn max_finger_nps(replay: &etternaonline_api::Replay) -> Option<f32> {
 	Some(max_finger_nps)
 }
 
+/// None if the column has no notes to judge
+fn analyze_column(hits: &[etterna::Hit]) -> Option<ColumnAnalysis> {
+	let mut judgement_counts = [0; 6];
+	for hit in hits {
+		let judgement_index = match hit.deviation().map(|d| etterna::J4.classify(d)) {
+			Some(etterna::TapJudgement::Marvelous) => 0,
+			Some(etterna::TapJudgement::Perfect) => 1,
+			Some(etterna::TapJudgement::Great) => 2,
+			Some(etterna::TapJudgement::Good) => 3,
+			Some(etterna::TapJudgement::Bad) => 4,
+			Some(etterna::TapJudgement::Miss) | None => 5,
+		};
+		judgement_counts[judgement_index] += 1;
+	}
+
+	let deviations = hits
+		.iter()
+		.filter_map(|hit| hit.deviation())
+		.collect::<Vec<_>>();
+	let num_deviations = deviations.len().max(1) as f32;
+	let mean_offset = deviations.iter().sum::<f32>() / num_deviations;
+	let variance = deviations
+		.iter()
+		.map(|deviation| (deviation - mean_offset).powi(2))
+		.sum::<f32>()
+		/ num_deviations;
+
+	Some(ColumnAnalysis {
+		judgement_counts,
+		mean_offset,
+		standard_deviation: variance.sqrt(),
+		wifescore: etterna::rescore_from_note_hits::<etterna::Wife3, _>(
+			hits.iter().copied(),
+			0,
+			0,
+			etterna::J4,
+		)?,
+	})
+}
+
+/// Lanes in between that have no notes are None, so that the column numbers stay correct
+fn analyze_columns(replay: &etternaonline_api::Replay) -> Option<Vec<Option<ColumnAnalysis>>> {
+	let mut column_hits = Vec::<Vec<etterna::Hit>>::new();
+	for note in &replay.notes {
+		match note.note_type? {
+			etterna::NoteType::Tap | etterna::NoteType::HoldHead | etterna::NoteType::Lift => {}
+			etterna::NoteType::HoldTail
+			| etterna::NoteType::Mine
+			| etterna::NoteType::Fake
+			| etterna::NoteType::Keysound => continue,
+		}
+
+		let lane = note.lane? as usize;
+		if column_hits.len() <= lane {
+			column_hits.resize(lane + 1, Vec::new());
+		}
+		column_hits[lane].push(note.hit);
+	}
+
+	Some(
+		column_hits
+			.iter()
+			.map(|hits| analyze_column(hits))
+			.collect(),
+	)
+}
+
 fn adjust_offset(replay: &etternaonline_api::Replay) -> (f32, etternaonline_api::Replay) {
 	let mean_offset = replay.mean_deviation();
 	let replay_zero_mean = etternaonline_api::Replay {
@@ -77,69 +162,86 @@ fn adjust_offset(replay: &etternaonline_api::Replay) -> (f32, etternaonline_api:
 }
 
 fn make_scoring_system_comparison(
-	score: &etternaonline_api::v1::ScoreData,
 	replay: &etternaonline_api::Replay,
 	replay_zero_mean: &etternaonline_api::Replay,
+	num_hit_mines: u32,
+	num_dropped_holds: u32,
 	judge: &etterna::Judge,
 ) -> Option<ScoringSystemComparison> {
 	Some(ScoringSystemComparison {
 		wife2_score: etternaonline_api::rescore::<etterna::NaiveScorer, etterna::Wife2>(
 			replay,
-			score.judgements.hit_mines,
-			score.judgements.let_go_holds + score.judgements.missed_holds,
+			num_hit_mines,
+			num_dropped_holds,
 			judge,
 		)?,
 		wife3_score: etternaonline_api::rescore::<etterna::NaiveScorer, etterna::Wife3>(
 			replay,
-			score.judgements.hit_mines,
-			score.judgements.let_go_holds + score.judgements.missed_holds,
+			num_hit_mines,
+			num_dropped_holds,
 			judge,
 		)?,
 		wife3_score_zero_mean: etternaonline_api::rescore::<etterna::NaiveScorer, etterna::Wife3>(
 			replay_zero_mean,
-			score.judgements.hit_mines,
-			score.judgements.let_go_holds + score.judgements.missed_holds,
+			num_hit_mines,
+			num_dropped_holds,
 			judge,
 		)?,
 	})
 }
 
+/// Mine hits and dropped holds are passed separately because they're not part of EO's replays
 pub fn do_replay_analysis(
-	score: &etternaonline_api::v1::ScoreData,
-	alternative_judge: Option<&etterna::Judge>,
+	replay: &etternaonline_api::Replay,
+	num_hit_mines: u32,
+	num_dropped_holds: u32,
+	alternative_judge: Option<&super::AlternativeJudge>,
 	draw_mean_instead_of_wifescore: bool,
+	show_all_judges: bool,
 ) -> Option<Result<ReplayAnalysis, Error>> {
-	let replay = score.replay.as_ref()?;
-
-	let r = replay_graph::generate_replay_graph(
+	let replay_graph = match replay_graph::generate_replay_graph(
 		replay,
-		"replay_graph.png",
 		draw_mean_instead_of_wifescore,
+		alternative_judge,
 	)
-	.transpose()?;
-	if let Err(e) = r {
-		return Some(Err(anyhow::anyhow!(e)));
-	}
+	.transpose()?
+	{
+		Ok(replay_graph) => replay_graph,
+		Err(e) => return Some(Err(anyhow::anyhow!(e))),
+	};
 
 	let (mean_offset, replay_zero_mean) = adjust_offset(replay);
 
-	Some(Ok(ReplayAnalysis {
-		replay_graph_path: "replay_graph.png",
-		scoring_system_comparison_j4: make_scoring_system_comparison(
-			score,
+	let make_scoring_system_comparison = |judge| {
+		make_scoring_system_comparison(
 			replay,
 			&replay_zero_mean,
-			etterna::J4,
-		)?,
+			num_hit_mines,
+			num_dropped_holds,
+			judge,
+		)
+	};
+
+	Some(Ok(ReplayAnalysis {
+		replay_graph,
+		scoring_system_comparison_j4: make_scoring_system_comparison(etterna::J4)?,
 		scoring_system_comparison_alternative: match alternative_judge {
-			Some(alternative_judge) => Some(make_scoring_system_comparison(
-				score,
-				replay,
-				&replay_zero_mean,
-				alternative_judge,
-			)?),
+			Some(alternative_judge) => {
+				Some(make_scoring_system_comparison(&alternative_judge.judge)?)
+			}
 			None => None,
 		},
+		// If one of the judges can't be rescored, leave out the table but keep the rest
+		all_judges: match show_all_judges {
+			true => crate::all_judges()
+				.iter()
+				.map(|judge| {
+					let comparison = make_scoring_system_comparison(judge)?;
+					Some((judge.name.to_string(), comparison))
+				})
+				.collect::<Option<Vec<_>>>(),
+			false => None,
+		},
 		fastest_finger_jackspeed: max_finger_nps(replay)?,
 		fastest_nps: fastest_nps(replay)?,
 		longest_100_combo: replay.longest_combo(|hit| hit.is_within_window(0.005)),
@@ -149,5 +251,70 @@ pub fn do_replay_analysis(
 			.longest_combo(|hit| hit.is_within_window(etterna::J4.perfect_window)),
 		longest_combo: replay.longest_combo(|hit| hit.is_within_window(etterna::J4.great_window)),
 		mean_offset,
+		columns: analyze_columns(replay),
 	}))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn note(lane: u8, hit: etterna::Hit) -> etternaonline_api::ReplayNote {
+		etternaonline_api::ReplayNote {
+			time: 0.0,
+			hit,
+			lane: Some(lane),
+			note_type: Some(etterna::NoteType::Tap),
+			tick: None,
+		}
+	}
+
+	fn hit(deviation: f32) -> etterna::Hit {
+		etterna::Hit::Hit { deviation }
+	}
+
+	#[test]
+	fn column_judgement_counts() {
+		// one of each judgement on J4
+		let hits = [
+			hit(0.01),
+			hit(-0.03),
+			hit(0.05),
+			hit(-0.1),
+			hit(0.15),
+			etterna::Hit::Miss,
+		];
+		let column = analyze_column(&hits).unwrap();
+		assert_eq!(column.judgement_counts, [1, 1, 1, 1, 1, 1]);
+
+		let column = analyze_column(&[hit(0.0), hit(0.02), hit(-0.022)]).unwrap();
+		assert_eq!(column.judgement_counts, [3, 0, 0, 0, 0, 0]);
+		assert!(column.wifescore.as_percent() > 99.0);
+	}
+
+	#[test]
+	fn column_mean_and_standard_deviation() {
+		// misses don't count towards the mean or the standard deviation
+		let column = analyze_column(&[hit(0.01), hit(0.03), etterna::Hit::Miss]).unwrap();
+		assert!((column.mean_offset - 0.02).abs() < 1e-6);
+		assert!((column.standard_deviation - 0.01).abs() < 1e-6);
+
+		let column = analyze_column(&[hit(-0.005), hit(-0.005)]).unwrap();
+		assert!((column.mean_offset + 0.005).abs() < 1e-6);
+		assert!(column.standard_deviation.abs() < 1e-6);
+	}
+
+	#[test]
+	fn columns_without_notes() {
+		let replay = etternaonline_api::Replay {
+			notes: vec![note(0, hit(0.0)), note(2, hit(0.01)), note(2, hit(0.02))],
+		};
+		let columns = analyze_columns(&replay).unwrap();
+		assert_eq!(columns.len(), 3);
+		assert!(columns[1].is_none());
+		assert_eq!(
+			columns[2].as_ref().unwrap().judgement_counts,
+			[2, 0, 0, 0, 0, 0]
+		);
+	}
+}
@@ -0,0 +1,198 @@
+//! Parses the replay files that Etterna saves in its ReplaysV2 folder, so that scores which aren't
+//! on EO can be analyzed too
+
+use crate::Error;
+
+/// Note rows in Etterna are 192nds, so there are 48 of them per beat
+const ROWS_PER_BEAT: f32 = 48.0;
+
+pub struct ReplayFile {
+	pub replay: etternaonline_api::Replay,
+	pub num_hit_mines: u32,
+	pub num_dropped_holds: u32,
+}
+
+/// Etterna's TapNoteType enum, as written in the fourth field of a replay line. Taps omit it
+fn parse_note_type(number: &str) -> Option<etterna::NoteType> {
+	Some(match number {
+		"1" => etterna::NoteType::Tap,
+		"2" => etterna::NoteType::HoldHead,
+		"3" => etterna::NoteType::HoldTail,
+		"4" => etterna::NoteType::Mine,
+		"5" => etterna::NoteType::Lift,
+		"7" => etterna::NoteType::Keysound,
+		"8" => etterna::NoteType::Fake,
+		_ => return None,
+	})
+}
+
+/// Each line is `row offset column [note type]` for a judged note, or `H row column [subtype]` for
+/// a dropped hold. Replay files only store note rows, so the chart is assumed to have a constant
+/// BPM to get the note times
+pub fn parse_replay_file(text: &str, bpm: f32) -> Result<ReplayFile, Error> {
+	let seconds_per_row = 60.0 / bpm / ROWS_PER_BEAT;
+
+	let mut notes = Vec::new();
+	let mut num_hit_mines = 0;
+	let mut num_dropped_holds = 0;
+	for (i, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() {
+			continue;
+		}
+		let invalid_line = || {
+			anyhow::anyhow!(
+				"Line {} isn't in Etterna's replay format: `{}`",
+				i + 1,
+				line
+			)
+		};
+
+		let fields = line.split_whitespace().collect::<Vec<_>>();
+		let (row, offset, column, note_type) = match *fields.as_slice() {
+			["H", ..] => {
+				num_dropped_holds += 1;
+				continue;
+			}
+			[row, offset, column] => (row, offset, column, etterna::NoteType::Tap),
+			[row, offset, column, note_type, ..] => (
+				row,
+				offset,
+				column,
+				parse_note_type(note_type).ok_or_else(invalid_line)?,
+			),
+			_ => return Err(invalid_line()),
+		};
+		let row: u32 = row.parse().map_err(|_| invalid_line())?;
+		let offset: f32 = offset.parse().map_err(|_| invalid_line())?;
+		// Rust parses "nan" and "inf", which would poison the rescored wifescore
+		if !offset.is_finite() {
+			return Err(invalid_line());
+		}
+		let column: u8 = column.parse().map_err(|_| invalid_line())?;
+
+		match note_type {
+			etterna::NoteType::Tap | etterna::NoteType::HoldHead | etterna::NoteType::Lift => {}
+			// Etterna only writes mines into the replay when they were hit
+			etterna::NoteType::Mine => {
+				num_hit_mines += 1;
+				continue;
+			}
+			etterna::NoteType::HoldTail | etterna::NoteType::Fake | etterna::NoteType::Keysound => {
+				continue
+			}
+		}
+
+		// Misses are saved with an offset of one second
+		let hit = if offset.abs() > crate::MAX_TIMING_WINDOW {
+			etterna::Hit::Miss
+		} else {
+			etterna::Hit::Hit { deviation: offset }
+		};
+		notes.push(etternaonline_api::ReplayNote {
+			time: row as f32 * seconds_per_row,
+			hit,
+			lane: Some(column),
+			note_type: Some(note_type),
+			tick: None,
+		});
+	}
+
+	if notes.is_empty() {
+		return Err(anyhow::anyhow!("The replay file doesn't contain any notes"));
+	}
+	// Etterna writes the notes sorted by row, but better be safe
+	notes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+
+	Ok(ReplayFile {
+		replay: etternaonline_api::Replay { notes },
+		num_hit_mines,
+		num_dropped_holds,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn deviation(note: &etternaonline_api::ReplayNote) -> Option<f32> {
+		match note.hit {
+			etterna::Hit::Hit { deviation } => Some(deviation),
+			etterna::Hit::Miss => None,
+		}
+	}
+
+	#[test]
+	fn fixture() {
+		let replay_file =
+			parse_replay_file(include_str!("../../tests/fixtures/replay_v2.txt"), 120.0).unwrap();
+		let notes = &replay_file.replay.notes;
+
+		// The hold tail and the fake aren't judged, and the mine only counts as hit mine
+		assert_eq!(notes.len(), 4);
+		assert_eq!(replay_file.num_hit_mines, 1);
+		assert_eq!(replay_file.num_dropped_holds, 2);
+
+		// A row without note type is a tap
+		assert!(matches!(notes[0].note_type, Some(etterna::NoteType::Tap)));
+		assert_eq!(notes[0].lane, Some(0));
+		assert_eq!(notes[0].time, 0.0);
+		assert!((deviation(&notes[0]).unwrap() - 0.012).abs() < 1e-6);
+
+		assert!(matches!(
+			notes[1].note_type,
+			Some(etterna::NoteType::HoldHead)
+		));
+		assert_eq!(notes[1].lane, Some(1));
+		// 48 rows are one beat, which is half a second at 120 BPM
+		assert_eq!(notes[1].time, 0.5);
+		assert!((deviation(&notes[1]).unwrap() + 0.03).abs() < 1e-6);
+
+		// Misses are saved with an offset of one second
+		assert_eq!(deviation(&notes[2]), None);
+		assert!(matches!(notes[2].note_type, Some(etterna::NoteType::Tap)));
+
+		assert!(matches!(notes[3].note_type, Some(etterna::NoteType::Tap)));
+		assert_eq!(notes[3].lane, Some(3));
+	}
+
+	#[test]
+	fn notes_are_sorted_by_time() {
+		let replay_file = parse_replay_file("96 0 1\n48 0 0\n", 120.0).unwrap();
+		let lanes = replay_file
+			.replay
+			.notes
+			.iter()
+			.map(|note| note.lane)
+			.collect::<Vec<_>>();
+		assert_eq!(lanes, [Some(0), Some(1)]);
+	}
+
+	#[test]
+	fn malformed_lines() {
+		for line in [
+			"48 0.01",
+			"48 x 0",
+			"48 nan 0",
+			"48 inf 0",
+			"48 0.01 0 6",
+			"-48 0.01 0",
+			"48 0.01 lane",
+			"hello",
+		] {
+			let error = parse_replay_file(&format!("0 0 0\n{}\n", line), 120.0)
+				.err()
+				.unwrap();
+			assert_eq!(
+				error.to_string(),
+				format!("Line 2 isn't in Etterna's replay format: `{}`", line)
+			);
+		}
+	}
+
+	#[test]
+	fn no_notes() {
+		assert!(parse_replay_file("", 120.0).is_err());
+		assert!(parse_replay_file("H 48 1\n0 0 2 4\n", 120.0).is_err());
+	}
+}
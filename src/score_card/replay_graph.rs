@@ -1,11 +1,14 @@
-//! Responsible for rendering the offset plot image in the score card, via the plotters library
+//! Responsible for rendering the offset plot and offset histogram image in the score card, via the
+//! plotters library
 
 #![allow(clippy::type_complexity)] // type complexity is not my fault
 
+use crate::render::rgb_buffer_to_png;
 use etterna::Wife;
 use plotters::{
 	prelude::*,
-	style::text_anchor::{HPos, Pos, VPos}, /*style::RGBAColor*/
+	style::text_anchor::{HPos, Pos, VPos},
+	style::RGBAColor,
 };
 use plotters_backend::BackendColor;
 
@@ -351,47 +354,198 @@ fn draw_mean_chart<'a, 'b>(
 	Ok(())
 }
 
+/// Draws how often each deviation occured, on top of the J4 judgement windows. The windows of the
+/// alternative judge are drawn as lines
+fn draw_offset_histogram(
+	canvas: &DrawingArea<
+		BitMapBackend<'_, plotters_bitmap::bitmap_pixel::RGBPixel>,
+		plotters::coord::Shift,
+	>,
+	replay: &etternaonline_api::Replay,
+	alternative_judge: Option<&super::AlternativeJudge>,
+) -> Result<(), Box<dyn std::error::Error>> {
+	const BIN_WIDTH: f32 = 0.002;
+
+	let deviations = replay
+		.notes
+		.iter()
+		.filter_map(|note| note.hit.deviation())
+		.collect::<Vec<_>>();
+	if deviations.is_empty() {
+		return Ok(());
+	}
+	let mean = deviations.iter().sum::<f32>() / deviations.len() as f32;
+	let standard_deviation = (deviations
+		.iter()
+		.map(|deviation| (deviation - mean).powi(2))
+		.sum::<f32>()
+		/ deviations.len() as f32)
+		.sqrt();
+
+	let max_deviation = match alternative_judge {
+		Some(alternative_judge) => {
+			f32::max(etterna::J4.bad_window, alternative_judge.judge.bad_window)
+		}
+		None => etterna::J4.bad_window,
+	};
+	let num_bins = (2.0 * max_deviation / BIN_WIDTH).ceil() as usize;
+	let mut bins = vec![0_u32; num_bins];
+	for &deviation in &deviations {
+		let bin = ((deviation + max_deviation) / BIN_WIDTH).max(0.0) as usize;
+		bins[bin.min(num_bins - 1)] += 1;
+	}
+	// UNWRAP: there's at least one deviation, so there's at least one bin
+	let y_max = *bins.iter().max().unwrap() as f32 * 1.15;
+
+	let mut chart = ChartBuilder::on(canvas)
+		.margin(5)
+		.x_label_area_size(25)
+		.build_cartesian_2d(-max_deviation..max_deviation, 0.0..y_max)?;
+
+	let judge_windows = |judge: &etterna::Judge| {
+		[
+			(judge.marvelous_window, MARVELOUS_COLOR),
+			(judge.perfect_window, PERFECT_COLOR),
+			(judge.great_window, GREAT_COLOR),
+			(judge.good_window, GOOD_COLOR),
+			(judge.bad_window, BAD_COLOR),
+		]
+	};
+
+	// Each J4 window is a band on both sides of the center, without the narrower windows inside
+	let mut inner_window = 0.0;
+	for (window, color) in judge_windows(etterna::J4) {
+		for (start, end) in [(-window, -inner_window), (inner_window, window)] {
+			chart.draw_series(std::iter::once(Rectangle::new(
+				[(start, 0.0), (end, y_max)],
+				color.mix(0.12).filled(),
+			)))?;
+		}
+		inner_window = window;
+	}
+
+	chart.draw_series(
+		bins.iter()
+			.enumerate()
+			.filter(|&(_, &count)| count > 0)
+			.map(|(i, &count)| {
+				let start = -max_deviation + i as f32 * BIN_WIDTH;
+				Rectangle::new(
+					[(start, 0.0), (start + BIN_WIDTH, count as f32)],
+					deviation_to_color(start + BIN_WIDTH / 2.0).filled(),
+				)
+			}),
+	)?;
+
+	let draw_vertical_line = |x: f32, color: RGBAColor, stroke_width: u32| {
+		chart.plotting_area().draw(&PathElement::new(
+			vec![(x, 0.0), (x, y_max)],
+			ShapeStyle {
+				color,
+				filled: false,
+				stroke_width,
+			},
+		))
+	};
+	if let Some(alternative_judge) = alternative_judge {
+		for (window, color) in judge_windows(&alternative_judge.judge) {
+			draw_vertical_line(-window, color.mix(0.6), 1)?;
+			draw_vertical_line(window, color.mix(0.6), 1)?;
+		}
+	}
+	draw_vertical_line(mean, WHITE.to_rgba(), 2)?;
+	draw_vertical_line(mean - standard_deviation, WHITE.mix(0.5), 1)?;
+	draw_vertical_line(mean + standard_deviation, WHITE.mix(0.5), 1)?;
+
+	let text_style = |hpos| TextStyle {
+		color: BackendColor {
+			rgb: (255, 255, 255),
+			alpha: 0.8,
+		},
+		pos: Pos::new(hpos, VPos::Top),
+		font: ("Open Sans", 18).into(),
+	};
+	chart
+		.configure_mesh()
+		.disable_mesh()
+		.disable_y_axis()
+		.axis_style(&WHITE.mix(0.5))
+		.x_label_style(text_style(HPos::Center))
+		.x_label_formatter(&|deviation| format!("{:.0}ms", deviation * 1000.0))
+		.x_labels(9)
+		.draw()?;
+
+	let judges_text = match alternative_judge {
+		Some(alternative_judge) => format!("Bands: J4, lines: {}", alternative_judge.name),
+		None => "Bands: J4".to_owned(),
+	};
+	canvas.draw(&Text::new(
+		format!(
+			"Mean: {:+.1}ms (thick line), standard deviation: {:.1}ms (thin lines). {}",
+			mean * 1000.0,
+			standard_deviation * 1000.0,
+			judges_text,
+		),
+		(15, 10),
+		text_style(HPos::Left),
+	))?;
+
+	Ok(())
+}
+
+const GRAPH_SIZE: (u32, u32) = (1290, 650);
+
 fn inner(
 	replay: &etternaonline_api::Replay,
-	output_path: &str,
 	draw_mean_instead_of_wifescore: bool,
-) -> Result<Option<()>, Box<dyn std::error::Error>> {
+	alternative_judge: Option<&super::AlternativeJudge>,
+) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
 	let stats = match gen_replay_stats(replay) {
 		Some(stats) => stats,
 		None => return Ok(None),
 	};
 
-	let canvas = BitMapBackend::new(output_path, (1290, 400)).into_drawing_area();
-	canvas.fill(&BLACK)?;
+	let mut rgb_buffer = vec![0; GRAPH_SIZE.0 as usize * GRAPH_SIZE.1 as usize * 3];
+	{
+		let root = BitMapBackend::with_buffer(&mut rgb_buffer, GRAPH_SIZE).into_drawing_area();
+		root.fill(&BLACK)?;
+		let (canvas, histogram_canvas) = root.split_vertically(400);
+
+		let x_range = 0.0..stats.chart_length;
 
-	let x_range = 0.0..stats.chart_length;
+		let dots_chart = draw_hit_dots(&replay, &stats, &x_range, &canvas)?;
 
-	let dots_chart = draw_hit_dots(&replay, &stats, &x_range, &canvas)?;
+		if draw_mean_instead_of_wifescore {
+			draw_mean_chart(&canvas, &x_range, &stats)?;
+		} else {
+			draw_wifescore_chart(&canvas, &x_range, &stats)?
+		}
 
-	if draw_mean_instead_of_wifescore {
-		draw_mean_chart(&canvas, &x_range, &stats)?;
-	} else {
-		draw_wifescore_chart(&canvas, &x_range, &stats)?
-	}
+		draw_mines(&canvas, &stats.mine_hit_locations, |time| {
+			dots_chart.backend_coord(&(time, 0.0)).0
+		})?;
 
-	draw_mines(&canvas, &stats.mine_hit_locations, |time| {
-		dots_chart.backend_coord(&(time, 0.0)).0
-	})?;
+		draw_offset_histogram(&histogram_canvas, replay, alternative_judge)?;
+
+		root.present()?;
+	}
 
-	Ok(Some(()))
+	Ok(Some(rgb_buffer_to_png(rgb_buffer, GRAPH_SIZE)?))
 }
 
 /// plotters did a GREAT fucking JOB of hiding their error types so that I'm **unable** to handle
 /// them. For that reason, this has a String as an error type.
+///
+/// Returns the image as PNG, or None if the replay lacks note types
 pub fn generate_replay_graph(
 	replay: &etternaonline_api::Replay,
-	output_path: &str,
 	draw_mean_instead_of_wifescore: bool,
-) -> Result<Option<()>, String> {
+	alternative_judge: Option<&super::AlternativeJudge>,
+) -> Result<Option<Vec<u8>>, String> {
 	// match inner(replay, output_path) {
 	// 	Ok(Some(())) => Ok(()),
 	// 	Ok(None) => Err(Error::InsufficientReplayData),
 	// 	Err(e) => Err(Error::PlottersError(e.to_string())),
 	// }
-	inner(replay, output_path, draw_mean_instead_of_wifescore).map_err(|e| e.to_string())
+	inner(replay, draw_mean_instead_of_wifescore, alternative_judge).map_err(|e| e.to_string())
 }
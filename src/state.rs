@@ -52,6 +52,8 @@ pub struct State {
 	pub web: etternaonline_api::web::Session,
 	pub eo2: eo2::Client,
 	pub noteskin_provider: commands::NoteskinProvider,
+	/// Shared with the blocking tasks that read simfiles
+	pub chart_library: std::sync::Arc<commands::ChartLibrary>,
 	// All lowercase
 	pub eo_usernames: crate::Cached<Vec<String>>,
 }
@@ -75,6 +77,15 @@ impl State {
 			}
 		}
 
+		// Reading every simfile of a big library takes a while, so keep it off the async workers
+		let chart_library_directory = config.chart_library_directory.clone();
+		let chart_library = tokio::task::spawn_blocking(move || {
+			commands::ChartLibrary::load(chart_library_directory.as_deref())
+		})
+		.await
+		.warn()
+		.unwrap_or_else(|| commands::ChartLibrary::load(None));
+
 		Self {
 			bot_start_time: std::time::Instant::now(),
 			v1: etternaonline_api::v1::Session::new(
@@ -92,6 +103,7 @@ impl State {
 			eo2: eo2::Client::new(),
 			auth,
 			web: web_session,
+			chart_library: std::sync::Arc::new(chart_library),
 			config,
 			data: std::sync::Mutex::new(config::Data::load()),
 			noteskin_provider: commands::NoteskinProvider::load(),